@@ -0,0 +1,500 @@
+//! Detach/attach for headless agents, like dtach.
+//!
+//! A headless relay-pty listens on an attach socket. `relay-pty attach` puts
+//! the user's terminal in raw mode and connects: the screen is repainted from
+//! the screen model, keystrokes go to the agent and output streams back until
+//! the detach key is pressed. The agent keeps running either way. One client
+//! can be attached at a time; `relay-pty attach --read-only` watches over the
+//! relay socket instead, as one of any number of observers.
+//!
+//! The socket speaks JSON lines ([`AttachRequest`] in, [`AttachEvent`] out).
+
+use crate::keys;
+use crate::modes::TerminalModes;
+use crate::output::{OutputHub, Utf8Decoder};
+use crate::protocol::{AttachEvent, AttachRequest, InjectRequest, InjectResponse, ObserveStart};
+use crate::screen::Screen;
+use crate::socket;
+use crate::tty;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::UnixStream;
+use tokio::select;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
+
+/// Key that detaches a client (Ctrl-\, as in dtach)
+pub const DEFAULT_DETACH_KEY: &str = "ctrl-\\";
+
+/// Undo modes the agent may have set on the client's terminal
+const RESET_TERMINAL: &str = "\x1b[0m\x1b[r\x1b[?7h\x1b[?25h\x1b[?1l\x1b[?2004l\x1b[?1049l";
+
+/// Work for the main loop on behalf of the attached client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachControl {
+    /// A client attached with a terminal of this size
+    Attached { rows: u16, cols: u16 },
+    /// Keystrokes for the agent
+    Input(Vec<u8>),
+    /// The client's terminal was resized
+    Resize { rows: u16, cols: u16 },
+    /// The client detached or disconnected
+    Detached,
+}
+
+/// Shared state for the attach server
+pub struct AttachContext {
+    /// Screen model (for the repaint on attach)
+    pub screen: Arc<Screen>,
+    /// Terminal modes the agent enabled (restored on the client)
+    pub modes: Arc<TerminalModes>,
+    /// Live PTY output
+    pub output: Arc<OutputHub>,
+    /// Requests to the main loop
+    pub control_tx: mpsc::Sender<AttachControl>,
+}
+
+/// Attach socket server
+pub struct AttachServer {
+    socket_path: String,
+    ctx: Arc<AttachContext>,
+    /// Whether a client is attached
+    attached: Arc<AtomicBool>,
+}
+
+impl AttachServer {
+    pub fn new(socket_path: String, ctx: AttachContext) -> Self {
+        Self {
+            socket_path,
+            ctx: Arc::new(ctx),
+            attached: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Accept attaching clients until the task is aborted
+    pub async fn run(self) -> Result<()> {
+        let listener = socket::bind(&self.socket_path)?;
+        info!("Attach socket listening at {}", self.socket_path);
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let ctx = Arc::clone(&self.ctx);
+                    let attached = Arc::clone(&self.attached);
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_client(stream, &ctx, &attached).await {
+                            warn!("Attach connection error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("Accept error: {}", e),
+            }
+        }
+    }
+}
+
+/// The attach socket that goes with a relay socket (`x.sock` -> `x.attach.sock`)
+pub fn default_socket_path(relay_socket: &str) -> String {
+    match relay_socket.strip_suffix(".sock") {
+        Some(stem) => format!("{}.attach.sock", stem),
+        None => format!("{}.attach", relay_socket),
+    }
+}
+
+/// The byte sent by a detach key name (`ctrl-\`, `ctrl-a`, ...)
+pub fn parse_detach_key(name: &str) -> Result<u8> {
+    match keys::encode_key(name, false)?.as_slice() {
+        [byte] => Ok(*byte),
+        _ => bail!("Detach key {:?} must be a single byte (e.g. ctrl-\\)", name),
+    }
+}
+
+async fn handle_client(
+    stream: UnixStream,
+    ctx: &AttachContext,
+    attached: &AtomicBool,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    let first = lines.next_line().await?.unwrap_or_default();
+    let (rows, cols) = match serde_json::from_str(&first) {
+        Ok(AttachRequest::Attach { rows, cols }) => (rows, cols),
+        _ => {
+            let message = "Expected an attach request first".to_string();
+            return write_line(&mut writer, &AttachEvent::Error { message }).await;
+        }
+    };
+    if attached.swap(true, Ordering::SeqCst) {
+        let message = "Another client is attached".to_string();
+        return write_line(&mut writer, &AttachEvent::Error { message }).await;
+    }
+
+    info!("Client attached ({}x{})", cols, rows);
+    let result = run_session(&mut lines, &mut writer, ctx, rows, cols).await;
+    attached.store(false, Ordering::SeqCst);
+    let _ = ctx.control_tx.send(AttachControl::Detached).await;
+    info!("Client detached");
+    result
+}
+
+/// Stream output to an attached client and its keystrokes to the agent
+async fn run_session<W: AsyncWrite + Unpin>(
+    lines: &mut Lines<BufReader<OwnedReadHalf>>,
+    writer: &mut W,
+    ctx: &AttachContext,
+    rows: u16,
+    cols: u16,
+) -> Result<()> {
+    // Repaint at the client's size; the main loop resizes the agent to match
+    ctx.screen.resize(rows, cols);
+    let (_, mut output_rx) = ctx.output.subscribe(0);
+    write_line(writer, &repaint(ctx)).await?;
+    control(ctx, AttachControl::Attached { rows, cols }).await?;
+
+    let mut decoder = Utf8Decoder::new();
+    loop {
+        select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                match serde_json::from_str(&line) {
+                    Ok(AttachRequest::Input { data }) => {
+                        control(ctx, AttachControl::Input(data)).await?;
+                    }
+                    Ok(AttachRequest::Resize { rows, cols }) => {
+                        control(ctx, AttachControl::Resize { rows, cols }).await?;
+                    }
+                    Ok(AttachRequest::Detach) => return Ok(()),
+                    Ok(AttachRequest::Attach { .. }) => {}
+                    Err(e) => warn!("Invalid attach request: {}", e),
+                }
+            }
+
+            chunk = output_rx.recv() => match chunk {
+                Ok(data) => {
+                    let data = decoder.decode(&data);
+                    if !data.is_empty() {
+                        write_line(writer, &AttachEvent::Output { data }).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Too slow to keep up: skip ahead and redraw from the screen model
+                    warn!("Attached client lagged by {} chunks, repainting", skipped);
+                    decoder = Utf8Decoder::new();
+                    write_line(writer, &repaint(ctx)).await?;
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Redraw the agent's screen and restore the input modes it enabled
+fn repaint(ctx: &AttachContext) -> AttachEvent {
+    let mut data = String::from_utf8_lossy(&ctx.screen.repaint()).into_owned();
+    if ctx.modes.bracketed_paste() {
+        data.push_str("\x1b[?2004h");
+    }
+    if ctx.modes.application_cursor() {
+        data.push_str("\x1b[?1h");
+    }
+    AttachEvent::Output { data }
+}
+
+async fn control(ctx: &AttachContext, request: AttachControl) -> Result<()> {
+    ctx.control_tx
+        .send(request)
+        .await
+        .map_err(|_| anyhow::anyhow!("relay-pty is shutting down"))
+}
+
+/// Write one JSON line
+async fn write_line<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> Result<()> {
+    let json = serde_json::to_string(message)?;
+    writer.write_all(json.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Attach this terminal to the agent until the detach key is pressed or
+/// relay-pty exits. `socket_path` is the attach socket, or with `read_only`
+/// the relay socket (watch as an observer: keystrokes other than the detach
+/// key are ignored)
+pub async fn run_client(socket_path: &str, detach_key: u8, read_only: bool) -> Result<()> {
+    if !tty::is_tty() {
+        bail!("relay-pty attach needs a terminal");
+    }
+    let stream = UnixStream::connect(socket_path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", socket_path))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    if read_only {
+        let observe = InjectRequest::Observe {
+            start: ObserveStart::Repaint,
+            replay_bytes: None,
+        };
+        write_line(&mut writer, &observe).await?;
+    } else {
+        let (rows, cols) = tty::terminal_size().unwrap_or((24, 80));
+        write_line(&mut writer, &AttachRequest::Attach { rows, cols }).await?;
+    }
+
+    tty::set_raw_mode().context("Failed to set raw mode")?;
+    let result = client_loop(&mut lines, &mut writer, detach_key, read_only).await;
+
+    // Leave the terminal as we found it, with the prompt on the bottom row
+    let rows = tty::terminal_size().map_or(24, |(rows, _)| rows);
+    let mut stdout = tokio::io::stdout();
+    let _ = stdout
+        .write_all(format!("{}\x1b[{};1H\r\n", RESET_TERMINAL, rows).as_bytes())
+        .await;
+    let _ = stdout.flush().await;
+    tty::restore_terminal();
+
+    eprintln!("[{}]", result?);
+    Ok(())
+}
+
+/// Output to draw from a line the server sent
+fn server_output(line: &str, read_only: bool) -> Result<Option<String>> {
+    if read_only {
+        return match serde_json::from_str(line)? {
+            InjectResponse::Output { data, .. } => Ok(data),
+            InjectResponse::Error { message } => bail!(message),
+            _ => Ok(None),
+        };
+    }
+    match serde_json::from_str(line)? {
+        AttachEvent::Output { data } => Ok(Some(data)),
+        AttachEvent::Error { message } => bail!(message),
+    }
+}
+
+/// Returns why the session ended
+async fn client_loop<W: AsyncWrite + Unpin>(
+    lines: &mut Lines<BufReader<OwnedReadHalf>>,
+    writer: &mut W,
+    detach_key: u8,
+    read_only: bool,
+) -> Result<&'static str> {
+    let mut sigwinch = signal(SignalKind::window_change())?;
+    let mut stdout = tokio::io::stdout();
+
+    let (stdin_tx, mut stdin_rx) = mpsc::channel::<Vec<u8>>(32);
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 1024];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if stdin_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    loop {
+        select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok("relay-pty exited");
+                };
+                if let Some(data) = server_output(&line, read_only)? {
+                    stdout.write_all(data.as_bytes()).await?;
+                    stdout.flush().await?;
+                }
+            }
+
+            Some(input) = stdin_rx.recv() => {
+                let detach = input.iter().position(|&b| b == detach_key);
+                if !read_only {
+                    let data = input[..detach.unwrap_or(input.len())].to_vec();
+                    if !data.is_empty() {
+                        write_line(writer, &AttachRequest::Input { data }).await?;
+                    }
+                    if detach.is_some() {
+                        write_line(writer, &AttachRequest::Detach).await?;
+                    }
+                }
+                if detach.is_some() {
+                    return Ok("detached");
+                }
+            }
+
+            // Observers can't resize the agent
+            _ = sigwinch.recv(), if !read_only => {
+                if let Some((rows, cols)) = tty::terminal_size() {
+                    write_line(writer, &AttachRequest::Resize { rows, cols }).await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::io::AsyncBufRead;
+
+    async fn read_event<R: AsyncBufRead + Unpin>(reader: &mut R) -> AttachEvent {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        serde_json::from_str(line.trim()).unwrap()
+    }
+
+    async fn connect(path: &str) -> (BufReader<OwnedReadHalf>, tokio::net::unix::OwnedWriteHalf) {
+        let stream = UnixStream::connect(path).await.unwrap();
+        let (reader, writer) = stream.into_split();
+        (BufReader::new(reader), writer)
+    }
+
+    #[test]
+    fn test_default_socket_path_and_detach_key() {
+        assert_eq!(
+            default_socket_path("/tmp/relay-pty-Alice.sock"),
+            "/tmp/relay-pty-Alice.attach.sock"
+        );
+        assert_eq!(default_socket_path("/tmp/agent"), "/tmp/agent.attach");
+
+        assert_eq!(parse_detach_key(DEFAULT_DETACH_KEY).unwrap(), 0x1c);
+        assert_eq!(parse_detach_key("ctrl-a").unwrap(), 0x01);
+        assert!(parse_detach_key("up").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_attach_repaint_input_and_detach() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("agent.attach.sock");
+        let path = path.to_string_lossy().to_string();
+
+        let screen = Arc::new(Screen::new(24, 80));
+        screen.feed(b"> draft");
+        let modes = Arc::new(TerminalModes::new());
+        modes.observe(b"\x1b[?2004h");
+        let output = Arc::new(OutputHub::new(1024));
+        let (control_tx, mut control_rx) = mpsc::channel(16);
+        let server = AttachServer::new(
+            path.clone(),
+            AttachContext {
+                screen,
+                modes,
+                output: Arc::clone(&output),
+                control_tx,
+            },
+        );
+        let server_handle = tokio::spawn(server.run());
+        while UnixStream::connect(&path).await.is_err() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let (mut reader, mut writer) = connect(&path).await;
+        write_line(
+            &mut writer,
+            &AttachRequest::Attach {
+                rows: 30,
+                cols: 100,
+            },
+        )
+        .await
+        .unwrap();
+
+        // The screen is repainted, with bracketed paste switched back on
+        match read_event(&mut reader).await {
+            AttachEvent::Output { data } => {
+                let copy = Screen::new(30, 100);
+                copy.feed(data.as_bytes());
+                assert_eq!(copy.snapshot().lines[0], "> draft");
+                assert!(data.ends_with("\x1b[?2004h"));
+            }
+            other => panic!("Expected output, got {:?}", other),
+        }
+        assert_eq!(
+            control_rx.recv().await.unwrap(),
+            AttachControl::Attached {
+                rows: 30,
+                cols: 100
+            }
+        );
+
+        // Only one client at a time
+        let (mut other_reader, mut other_writer) = connect(&path).await;
+        write_line(
+            &mut other_writer,
+            &AttachRequest::Attach { rows: 24, cols: 80 },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            read_event(&mut other_reader).await,
+            AttachEvent::Error { .. }
+        ));
+
+        // Live output streams to the client, keystrokes and resizes go to the main loop
+        output.publish(b" more");
+        assert_eq!(
+            read_event(&mut reader).await,
+            AttachEvent::Output {
+                data: " more".to_string()
+            }
+        );
+        let input = AttachRequest::Input {
+            data: b"\r".to_vec(),
+        };
+        write_line(&mut writer, &input).await.unwrap();
+        assert_eq!(
+            control_rx.recv().await.unwrap(),
+            AttachControl::Input(b"\r".to_vec())
+        );
+        write_line(
+            &mut writer,
+            &AttachRequest::Resize {
+                rows: 40,
+                cols: 120,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            control_rx.recv().await.unwrap(),
+            AttachControl::Resize {
+                rows: 40,
+                cols: 120
+            }
+        );
+
+        write_line(&mut writer, &AttachRequest::Detach)
+            .await
+            .unwrap();
+        assert_eq!(control_rx.recv().await.unwrap(), AttachControl::Detached);
+
+        // Another client can attach now
+        let (mut reader, mut writer) = connect(&path).await;
+        write_line(&mut writer, &AttachRequest::Attach { rows: 24, cols: 80 })
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_event(&mut reader).await,
+            AttachEvent::Output { .. }
+        ));
+
+        server_handle.abort();
+    }
+}
@@ -0,0 +1,428 @@
+//! Local escape commands for the human at the terminal.
+//!
+//! Like ssh's `~.`, pressing `Ctrl-]` followed by a command key controls
+//! relay-pty itself instead of the child. Neither key reaches the child and
+//! the command's output is written straight to the user's terminal.
+//! `Ctrl-]` twice sends a literal `Ctrl-]` to the child.
+//!
+//! In review mode the same keys approve, edit or reject the message awaiting
+//! review; editing reads a replacement line locally, echoing it without
+//! involving the child.
+
+use crate::inject::Injector;
+use crate::protocol::QueuedMessage;
+use crate::queue::MessageQueue;
+use crate::review::ReviewDecision;
+use std::time::Instant;
+
+/// Escape key (`Ctrl-]`)
+pub const ESCAPE_KEY: u8 = 0x1d;
+
+/// A command entered after the escape key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeCommand {
+    /// `p` - pause or resume injection
+    TogglePause,
+    /// `l` - list queued messages
+    ListQueue,
+    /// `d` - drop the next message that would be injected
+    DropNext,
+    /// `e` - send Enter to the child
+    SendEnter,
+    /// `s` - show status
+    Status,
+    /// `a` - approve the message awaiting review
+    ApproveReview,
+    /// `m` - replace the body of the message awaiting review (reads a line)
+    EditReview,
+    /// `x` - reject the message awaiting review
+    RejectReview,
+    /// `?` or any other key - show the available commands
+    Help,
+}
+
+impl EscapeCommand {
+    fn from_key(key: u8) -> Self {
+        match key {
+            b'p' | b'P' => EscapeCommand::TogglePause,
+            b'l' | b'L' => EscapeCommand::ListQueue,
+            b'd' | b'D' => EscapeCommand::DropNext,
+            b'e' | b'E' => EscapeCommand::SendEnter,
+            b's' | b'S' => EscapeCommand::Status,
+            b'a' | b'A' => EscapeCommand::ApproveReview,
+            b'm' | b'M' => EscapeCommand::EditReview,
+            b'x' | b'X' => EscapeCommand::RejectReview,
+            _ => EscapeCommand::Help,
+        }
+    }
+}
+
+/// What to do with a piece of stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StdinAction {
+    /// Pass these bytes to the child
+    Forward(Vec<u8>),
+    /// Run a local command
+    Command(EscapeCommand),
+    /// Show these bytes locally (echo of a line being entered)
+    Echo(Vec<u8>),
+    /// A line entered after `EditReview` (`None` if cancelled with Esc or Ctrl-C)
+    Line(Option<String>),
+}
+
+/// Splits stdin into bytes for the child and escape commands.
+/// The escape key may arrive at the end of one read and the command key in the next.
+#[derive(Debug, Default)]
+pub struct EscapeParser {
+    armed: bool,
+    /// Line being entered locally, if reading one
+    line: Option<Vec<u8>>,
+}
+
+impl EscapeParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop reading a line (e.g. nothing is awaiting review after all)
+    pub fn cancel_line(&mut self) {
+        self.line = None;
+    }
+
+    /// Process one read from stdin
+    pub fn feed(&mut self, data: &[u8]) -> Vec<StdinAction> {
+        let mut actions = Vec::new();
+        let mut forward = Vec::new();
+
+        for &byte in data {
+            if let Some(ref mut line) = self.line {
+                match byte {
+                    b'\r' | b'\n' => {
+                        let text = String::from_utf8_lossy(line).into_owned();
+                        actions.push(StdinAction::Echo(b"\r\n".to_vec()));
+                        actions.push(StdinAction::Line(Some(text)));
+                        self.line = None;
+                    }
+                    // Esc or Ctrl-C cancels
+                    0x1b | 0x03 => {
+                        actions.push(StdinAction::Echo(b"\r\n".to_vec()));
+                        actions.push(StdinAction::Line(None));
+                        self.line = None;
+                    }
+                    // Backspace removes one character (all its UTF-8 bytes)
+                    0x7f | 0x08 => {
+                        while line.pop().is_some_and(|b| (b & 0xC0) == 0x80) {}
+                        actions.push(StdinAction::Echo(b"\x08 \x08".to_vec()));
+                    }
+                    _ if byte >= 0x20 => {
+                        line.push(byte);
+                        actions.push(StdinAction::Echo(vec![byte]));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if self.armed {
+                self.armed = false;
+                if byte == ESCAPE_KEY {
+                    forward.push(ESCAPE_KEY);
+                } else {
+                    if !forward.is_empty() {
+                        actions.push(StdinAction::Forward(std::mem::take(&mut forward)));
+                    }
+                    let cmd = EscapeCommand::from_key(byte);
+                    if cmd == EscapeCommand::EditReview {
+                        // Everything up to Enter is the new body, not input for the child
+                        self.line = Some(Vec::new());
+                    }
+                    actions.push(StdinAction::Command(cmd));
+                }
+            } else if byte == ESCAPE_KEY {
+                self.armed = true;
+            } else {
+                forward.push(byte);
+            }
+        }
+
+        if !forward.is_empty() {
+            actions.push(StdinAction::Forward(forward));
+        }
+        actions
+    }
+}
+
+/// Run a command that only touches relay state, returning the lines to show.
+/// `SendEnter` needs the PTY and is handled by the caller.
+pub async fn execute(cmd: EscapeCommand, injector: &Injector, queue: &MessageQueue) -> Vec<String> {
+    match cmd {
+        EscapeCommand::TogglePause => {
+            let paused = !injector.is_paused();
+            injector.set_paused(paused);
+            vec![if paused {
+                "Injection paused (messages keep queueing)".to_string()
+            } else {
+                "Injection resumed".to_string()
+            }]
+        }
+        EscapeCommand::ListQueue => {
+            let pending = queue.pending().await;
+            if pending.is_empty() {
+                return vec!["Queue is empty".to_string()];
+            }
+            let mut lines = vec![format!("{} queued:", pending.len())];
+            lines.extend(pending.iter().map(|msg| {
+                let entry = msg.queue_entry();
+                let due = entry
+                    .due_in_ms
+                    .map(|ms| format!(" due in {}s", ms / 1000))
+                    .unwrap_or_default();
+                format!(
+                    "  {} from {} p{}{}: {}",
+                    entry.id, entry.from, entry.priority, due, entry.preview
+                )
+            }));
+            lines
+        }
+        EscapeCommand::DropNext => {
+            let next = queue
+                .pending()
+                .await
+                .into_iter()
+                .next()
+                .filter(|msg| msg.is_due(Instant::now()));
+            let Some(next) = next else {
+                return vec!["No message ready to inject".to_string()];
+            };
+            match queue.cancel(&next.id).await {
+                Ok(msg) => vec![format!("Dropped {} from {}", msg.id, msg.from)],
+                Err(e) => vec![e.to_string()],
+            }
+        }
+        EscapeCommand::Status => {
+            let stats = queue.stats().await;
+            let injection = if injector.is_paused() {
+                "paused"
+            } else {
+                "running"
+            };
+            let agent = if injector.check_idle() {
+                "idle"
+            } else {
+                "busy"
+            };
+            let user = if injector.user_active() {
+                "typing"
+            } else {
+                "quiet"
+            };
+            vec![format!(
+                "injection {}, agent {}, {} ready, {} scheduled, user {}",
+                injection, agent, stats.ready, stats.scheduled, user
+            )]
+        }
+        EscapeCommand::ApproveReview => decide(injector, ReviewDecision::Approve),
+        EscapeCommand::RejectReview => decide(
+            injector,
+            ReviewDecision::Reject("rejected at the terminal".to_string()),
+        ),
+        EscapeCommand::EditReview => match injector.awaiting_review() {
+            Some(msg) => vec![format!(
+                "New body for {} (Enter to send, Esc to cancel):",
+                msg.id
+            )],
+            None => no_review(injector),
+        },
+        EscapeCommand::SendEnter => vec!["Sent Enter".to_string()],
+        EscapeCommand::Help => help(),
+    }
+}
+
+/// Finish an `EditReview` with the line the user entered
+pub fn finish_edit(injector: &Injector, line: Option<String>) -> Vec<String> {
+    match line {
+        Some(body) => decide(injector, ReviewDecision::Edit(body)),
+        None => vec!["Edit cancelled".to_string()],
+    }
+}
+
+/// Lines announcing a message that needs review
+pub fn review_prompt(msg: &QueuedMessage) -> Vec<String> {
+    let mut lines = vec![format!(
+        "Review message {} from {} (priority {}):",
+        msg.id, msg.from, msg.priority
+    )];
+    lines.extend(msg.body.lines().map(|line| format!("  {}", line)));
+    lines.push("Ctrl-] a approve | Ctrl-] m edit | Ctrl-] x reject".to_string());
+    lines
+}
+
+/// Apply a decision to the message awaiting review
+fn decide(injector: &Injector, decision: ReviewDecision) -> Vec<String> {
+    let (Some(desk), Some(msg)) = (injector.review_desk(), injector.awaiting_review()) else {
+        return no_review(injector);
+    };
+    let done = match decision {
+        ReviewDecision::Approve => "Approved",
+        ReviewDecision::Edit(_) => "Edited and approved",
+        ReviewDecision::Reject(_) => "Rejected",
+    };
+    match desk.decide(&msg.id, decision) {
+        Ok(()) => vec![format!("{} {}", done, msg.id)],
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+fn no_review(injector: &Injector) -> Vec<String> {
+    if injector.review_desk().is_some() {
+        vec!["No message is awaiting review".to_string()]
+    } else {
+        vec!["Review mode is off (start with --review)".to_string()]
+    }
+}
+
+/// The list of escape commands
+fn help() -> Vec<String> {
+    vec![
+        "Ctrl-] commands:".to_string(),
+        "  p  pause/resume injection".to_string(),
+        "  l  list queued messages".to_string(),
+        "  d  drop the next message".to_string(),
+        "  e  send Enter to the agent".to_string(),
+        "  s  show status".to_string(),
+        "  a  approve the message awaiting review".to_string(),
+        "  m  edit the message awaiting review".to_string(),
+        "  x  reject the message awaiting review".to_string(),
+        "  Ctrl-]  send a literal Ctrl-]".to_string(),
+    ]
+}
+
+/// Format lines for a terminal in raw mode
+pub fn render(lines: &[String]) -> Vec<u8> {
+    let mut out = String::from("\r\n");
+    for line in lines {
+        out.push_str("[relay-pty] ");
+        out.push_str(line);
+        out.push_str("\r\n");
+    }
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::TerminalModes;
+    use crate::protocol::{Config, QueuedMessage};
+    use std::sync::Arc;
+    use tokio::sync::{broadcast, mpsc};
+
+    #[test]
+    fn test_parser_splits_commands_from_input() {
+        let mut parser = EscapeParser::new();
+        assert_eq!(
+            parser.feed(b"ab\x1dpcd"),
+            vec![
+                StdinAction::Forward(b"ab".to_vec()),
+                StdinAction::Command(EscapeCommand::TogglePause),
+                StdinAction::Forward(b"cd".to_vec()),
+            ]
+        );
+
+        // Escape key split across reads
+        assert_eq!(
+            parser.feed(b"x\x1d"),
+            vec![StdinAction::Forward(b"x".to_vec())]
+        );
+        assert_eq!(
+            parser.feed(b"l"),
+            vec![StdinAction::Command(EscapeCommand::ListQueue)]
+        );
+
+        // Doubled escape sends it through; unknown keys show help
+        assert_eq!(
+            parser.feed(b"\x1d\x1d\x1dz"),
+            vec![
+                StdinAction::Forward(vec![ESCAPE_KEY]),
+                StdinAction::Command(EscapeCommand::Help),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_review_reads_a_line() {
+        let mut parser = EscapeParser::new();
+        assert_eq!(
+            parser.feed(b"\x1dmHey\x7f"),
+            vec![
+                StdinAction::Command(EscapeCommand::EditReview),
+                StdinAction::Echo(b"H".to_vec()),
+                StdinAction::Echo(b"e".to_vec()),
+                StdinAction::Echo(b"y".to_vec()),
+                StdinAction::Echo(b"\x08 \x08".to_vec()),
+            ]
+        );
+        assert_eq!(
+            parser.feed("é\rnext".as_bytes()),
+            vec![
+                StdinAction::Echo(vec![0xc3]),
+                StdinAction::Echo(vec![0xa9]),
+                StdinAction::Echo(b"\r\n".to_vec()),
+                StdinAction::Line(Some("Heé".to_string())),
+                StdinAction::Forward(b"next".to_vec()),
+            ]
+        );
+
+        parser.feed(b"\x1dmabc");
+        assert_eq!(
+            parser.feed(b"\x1b"),
+            vec![StdinAction::Echo(b"\r\n".to_vec()), StdinAction::Line(None)]
+        );
+
+        parser.feed(b"\x1dm");
+        parser.cancel_line();
+        assert_eq!(parser.feed(b"x"), vec![StdinAction::Forward(b"x".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_pause_and_drop() {
+        let (pty_tx, _pty_rx) = mpsc::channel(1);
+        let (response_tx, _response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let injector = Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            Config::default(),
+            Arc::new(TerminalModes::new()),
+        );
+
+        execute(EscapeCommand::TogglePause, &injector, &queue).await;
+        assert!(injector.is_paused());
+        execute(EscapeCommand::TogglePause, &injector, &queue).await;
+        assert!(!injector.is_paused());
+
+        for (id, priority) in [("low", 5), ("high", 0)] {
+            let msg = QueuedMessage::new(
+                id.to_string(),
+                "Alice".to_string(),
+                "Hi".to_string(),
+                priority,
+            );
+            queue.enqueue(msg).await;
+        }
+
+        let lines = execute(EscapeCommand::DropNext, &injector, &queue).await;
+        assert_eq!(lines, vec!["Dropped high from Alice".to_string()]);
+
+        let lines = execute(EscapeCommand::ListQueue, &injector, &queue).await;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("low from Alice p5"));
+    }
+
+    #[test]
+    fn test_render_uses_crlf() {
+        assert_eq!(
+            render(&["one".to_string()]),
+            b"\r\n[relay-pty] one\r\n".to_vec()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Sequenced delivery of parsed relay events to socket subscribers.
+//!
+//! Relay commands, continuity commands and stale outbox notifications are
+//! numbered as they are produced and kept in a bounded buffer until a client
+//! acknowledges them. Subscribing replays everything still unacknowledged, so
+//! a daemon that restarts and reconnects picks up spawn and message commands
+//! it never confirmed.
+
+use crate::protocol::RelayEvent;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// Default number of unacknowledged events kept for replay
+pub const DEFAULT_EVENT_BUFFER: usize = 1000;
+
+/// Number of live events a subscriber may fall behind before it starts lagging
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A relay event tagged with its sequence number
+#[derive(Debug, Clone)]
+pub struct SequencedEvent {
+    /// Monotonically increasing sequence number (starts at 1)
+    pub seq: u64,
+    /// The event itself
+    pub event: RelayEvent,
+}
+
+struct EventLogInner {
+    /// Sequence number for the next event
+    next_seq: u64,
+    /// Events not yet acknowledged, oldest first
+    unacked: VecDeque<SequencedEvent>,
+    /// Events were dropped for a subscriber since the buffer last had room
+    /// (so the warning is logged once per overflow, not once per event)
+    overflowing: bool,
+}
+
+/// Bounded log of unacknowledged relay events
+pub struct EventLog {
+    inner: Mutex<EventLogInner>,
+    /// Live events for subscribers
+    tx: broadcast::Sender<SequencedEvent>,
+    /// Maximum number of unacknowledged events retained
+    capacity: usize,
+}
+
+impl EventLog {
+    /// Create an event log retaining up to `capacity` unacknowledged events
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            inner: Mutex::new(EventLogInner {
+                next_seq: 1,
+                unacked: VecDeque::new(),
+                overflowing: false,
+            }),
+            tx,
+            capacity,
+        }
+    }
+
+    /// Record a new event and broadcast it to subscribers.
+    /// Returns the sequence number assigned to it.
+    pub fn push(&self, event: RelayEvent) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+
+        let sequenced = SequencedEvent { seq, event };
+        inner.unacked.push_back(sequenced.clone());
+
+        if inner.unacked.len() > self.capacity {
+            if let Some(dropped) = inner.unacked.pop_front() {
+                // With no subscriber nobody can ack, so dropping is expected
+                if !inner.overflowing && self.tx.receiver_count() > 0 {
+                    warn!(
+                        "Event buffer full ({}), dropping unacknowledged events from {}",
+                        self.capacity, dropped.seq
+                    );
+                    inner.overflowing = true;
+                } else {
+                    debug!("Event buffer full, dropping event {}", dropped.seq);
+                }
+            }
+        }
+
+        // Broadcast under the lock so subscribe() can't miss or duplicate it.
+        // An error only means there are no subscribers right now.
+        let _ = self.tx.send(sequenced);
+        seq
+    }
+
+    /// Subscribe to live events, returning all unacknowledged events that
+    /// precede the first live one.
+    pub fn subscribe(&self) -> (Vec<SequencedEvent>, broadcast::Receiver<SequencedEvent>) {
+        let inner = self.inner.lock().unwrap();
+        let rx = self.tx.subscribe();
+        (inner.unacked.iter().cloned().collect(), rx)
+    }
+
+    /// Unacknowledged events with a sequence number greater than `seq`
+    /// (used to fill gaps after a lagging subscriber drops live events)
+    pub fn since(&self, seq: u64) -> Vec<SequencedEvent> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .unacked
+            .iter()
+            .filter(|e| e.seq > seq)
+            .cloned()
+            .collect()
+    }
+
+    /// Acknowledge every event up to and including `seq`.
+    /// Returns the number of events released from the buffer.
+    pub fn ack(&self, seq: u64) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.unacked.len();
+        inner.unacked.retain(|e| e.seq > seq);
+        let released = before - inner.unacked.len();
+        if inner.unacked.len() < self.capacity {
+            inner.overflowing = false;
+        }
+        debug!("Acked events up to {} ({} released)", seq, released);
+        released
+    }
+
+    /// Number of events still awaiting acknowledgement
+    pub fn pending(&self) -> usize {
+        self.inner.lock().unwrap().unacked.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ContinuityCommand, ParsedRelayCommand};
+
+    fn message_event(body: &str) -> RelayEvent {
+        RelayEvent::Command(ParsedRelayCommand::new_message(
+            "Alice".to_string(),
+            "Bob".to_string(),
+            body.to_string(),
+            format!("->relay:Bob {}", body),
+        ))
+    }
+
+    #[test]
+    fn test_push_assigns_increasing_sequence_numbers() {
+        let log = EventLog::new(10);
+        assert_eq!(log.push(message_event("one")), 1);
+        assert_eq!(
+            log.push(RelayEvent::Continuity(ContinuityCommand::new(
+                "save".to_string(),
+                "state".to_string()
+            ))),
+            2
+        );
+        assert_eq!(log.pending(), 2);
+    }
+
+    #[test]
+    fn test_ack_is_cumulative() {
+        let log = EventLog::new(10);
+        log.push(message_event("one"));
+        log.push(message_event("two"));
+        log.push(message_event("three"));
+
+        assert_eq!(log.ack(2), 2);
+        assert_eq!(log.pending(), 1);
+
+        let (replay, _rx) = log.subscribe();
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].seq, 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replays_unacked_then_streams_live() {
+        let log = EventLog::new(10);
+        log.push(message_event("before"));
+
+        let (replay, mut rx) = log.subscribe();
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].seq, 1);
+
+        log.push(message_event("after"));
+        let live = rx.recv().await.unwrap();
+        assert_eq!(live.seq, 2);
+    }
+
+    #[test]
+    fn test_buffer_drops_oldest_when_full() {
+        let log = EventLog::new(2);
+        log.push(message_event("one"));
+        log.push(message_event("two"));
+        log.push(message_event("three"));
+
+        let seqs: Vec<u64> = log.since(0).iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_overflow_warns_once_per_episode_with_subscriber() {
+        let log = EventLog::new(1);
+        log.push(message_event("one"));
+        log.push(message_event("two"));
+        // Nobody subscribed: not an overflow worth warning about
+        assert!(!log.inner.lock().unwrap().overflowing);
+
+        let (_, _rx) = log.subscribe();
+        log.push(message_event("three"));
+        assert!(log.inner.lock().unwrap().overflowing);
+        log.push(message_event("four"));
+        assert!(log.inner.lock().unwrap().overflowing);
+
+        // An ack makes room and ends the episode
+        log.ack(4);
+        assert!(!log.inner.lock().unwrap().overflowing);
+    }
+}
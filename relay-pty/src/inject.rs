@@ -1,21 +1,75 @@
 //! Injection logic for delivering messages to the agent.
 //!
 //! Handles:
-//! - Waiting for injection window (agent idle)
-//! - Writing message to PTY
-//! - Verifying injection success
+//! - Waiting for injection window (agent idle), or interrupting a busy agent
+//!   for `interrupt` messages
+//! - Writing message to PTY (as a bracketed paste when the child enables it),
+//!   in paced chunks, with oversized bodies saved to a file
+//! - Verifying injection success (echo or agent activity)
 //! - Retry logic
+//! - Capturing the agent's reply for `capture_response` messages
 
-use crate::parser::ParseResult;
-use crate::protocol::{Config, InjectStatus, QueuedMessage};
+use crate::modes::{bracketed, TerminalModes};
+use crate::parser::{strip_ansi, ParseResult};
+use crate::profile::PasteStyle;
+use crate::protocol::{prefix_chars, short_id, Config, IdleMethod, InjectStatus, QueuedMessage};
 use crate::queue::MessageQueue;
-use anyhow::Result;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::review::{ReviewDecision, ReviewDesk};
+use crate::screen::Screen;
+use crate::template::MessageTemplate;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, error, info, warn};
 
+/// Characters of the formatted message that must be echoed for a prefix match
+const ECHO_PREFIX_CHARS: usize = 40;
+
+/// Alphanumeric characters of the body compared for a fuzzy echo match
+const FUZZY_ECHO_CHARS: usize = 24;
+
+/// Bodies with fewer alphanumeric characters are too short to fuzzy-match reliably
+const FUZZY_ECHO_MIN_CHARS: usize = 8;
+
+/// How long to wait for the agent's reply when the sender gives no timeout
+const DEFAULT_CAPTURE_TIMEOUT_MS: u64 = 120_000;
+
+/// Raw output kept for a captured reply (later output is dropped)
+const MAX_CAPTURE_BYTES: usize = 256 * 1024;
+
+/// How long to wait for the agent to go idle before injecting anyway
+const WINDOW_TIMEOUT_MS: u64 = 10_000;
+
+/// How long to wait for the prompt after interrupting the agent
+const INTERRUPT_TIMEOUT_MS: u64 = 10_000;
+
+/// Rows around the cursor searched for a message left in the input line
+const INPUT_LINE_ROWS: usize = 2;
+
+/// What became of a message written to the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    /// Echoed or acted on (or assumed, with verification off)
+    Delivered,
+    /// Output arrived but none of it showed the message: typing it again
+    /// could give the agent a duplicate, so it counts as delivered
+    Unverified,
+    /// Nothing reached the terminal at all: safe to type it again
+    Lost,
+}
+
+/// Output collected for the reply to a `capture_response` message
+struct ReplyCapture {
+    /// Message the reply is to
+    id: String,
+    /// Raw output since Enter
+    output: String,
+}
+
 /// Injection manager
 pub struct Injector {
     /// Channel for sending data to PTY
@@ -26,14 +80,34 @@ pub struct Injector {
     config: Config,
     /// Whether agent is currently idle
     is_idle: AtomicBool,
+    /// How the parser last detected idleness
+    idle_method: std::sync::Mutex<IdleMethod>,
     /// Timestamp of last output (ms since epoch)
     last_output_ms: AtomicU64,
     /// Timestamp of last injection (ms since epoch)
     last_injection_ms: AtomicU64,
     /// Recent output buffer for verification
     recent_output: Mutex<String>,
+    /// Bytes of output recorded so far (recent_output is trimmed, this is not)
+    output_bytes: AtomicUsize,
+    /// Reply being captured (None when not capturing)
+    capture: Mutex<Option<ReplyCapture>>,
     /// Whether an auto-suggestion is currently visible (blocks injection)
     auto_suggestion_visible: AtomicBool,
+    /// Terminal modes the child has enabled (bracketed paste)
+    modes: Arc<TerminalModes>,
+    /// Timestamp of the last keystroke from the human (ms since epoch, 0 = never)
+    last_input_ms: AtomicU64,
+    /// Whether a human terminal is attached to stdin
+    human_attached: AtomicBool,
+    /// Injection paused by the operator
+    paused: AtomicBool,
+    /// Review desk (review mode: a human approves each new message)
+    review: Option<Arc<ReviewDesk>>,
+    /// Screen model (to spot a message left in the input line)
+    screen: Option<Arc<Screen>>,
+    /// Files holding spilled bodies, by message ID
+    spilled: Mutex<HashMap<String, PathBuf>>,
 }
 
 // Injector is Send+Sync safe
@@ -42,22 +116,54 @@ unsafe impl Sync for Injector {}
 
 impl Injector {
     /// Create a new injector
-    pub fn new(pty_tx: mpsc::Sender<Vec<u8>>, queue: Arc<MessageQueue>, config: Config) -> Self {
+    pub fn new(
+        pty_tx: mpsc::Sender<Vec<u8>>,
+        queue: Arc<MessageQueue>,
+        config: Config,
+        modes: Arc<TerminalModes>,
+    ) -> Self {
         Self {
             pty_tx,
             queue,
             config,
             is_idle: AtomicBool::new(false),
+            idle_method: std::sync::Mutex::new(IdleMethod::Buffer),
             last_output_ms: AtomicU64::new(current_timestamp_ms()),
             last_injection_ms: AtomicU64::new(0), // No injection yet
             recent_output: Mutex::new(String::new()),
+            output_bytes: AtomicUsize::new(0),
+            capture: Mutex::new(None),
             auto_suggestion_visible: AtomicBool::new(false),
+            modes,
+            last_input_ms: AtomicU64::new(0),
+            human_attached: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            review: None,
+            screen: None,
+            spilled: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Check the screen for messages left unsubmitted in the input line
+    pub fn with_screen(mut self, screen: Arc<Screen>) -> Self {
+        self.screen = Some(screen);
+        self
+    }
+
+    /// Enable review mode: hold each new message until a reviewer decides
+    pub fn with_review(mut self, desk: Arc<ReviewDesk>) -> Self {
+        self.review = Some(desk);
+        self
+    }
+
     /// Update idle state based on parser result
     pub fn update_from_parse(&self, result: &ParseResult) {
         if result.is_idle || result.ready_signal {
+            *self.idle_method.lock().unwrap() = match result.idle_method {
+                Some(method) => method,
+                None if result.ready_signal => IdleMethod::ReadySignal,
+                None => IdleMethod::Buffer,
+            };
             self.is_idle.store(true, Ordering::SeqCst);
         }
     }
@@ -78,12 +184,19 @@ impl Injector {
 
         self.last_output_ms
             .store(current_timestamp_ms(), Ordering::SeqCst);
-        if !is_relay_echo(output) {
+        if !self.config.template.is_echo(output) {
             self.is_idle.store(false, Ordering::SeqCst);
         }
 
+        if let Some(capture) = self.capture.lock().await.as_mut() {
+            if capture.output.len() < MAX_CAPTURE_BYTES {
+                capture.output.push_str(output);
+            }
+        }
+
         let mut recent = self.recent_output.lock().await;
         recent.push_str(output);
+        self.output_bytes.fetch_add(output.len(), Ordering::SeqCst);
 
         // Limit buffer size (must find valid UTF-8 char boundary)
         if recent.len() > 10000 {
@@ -101,15 +214,20 @@ impl Injector {
     /// Check if agent is idle (based on timeout or explicit signal)
     /// Returns false if an auto-suggestion is currently visible (blocks injection)
     pub fn check_idle(&self) -> bool {
+        self.idle_method().is_some()
+    }
+
+    /// How the agent was found idle, or None while it is busy
+    pub fn idle_method(&self) -> Option<IdleMethod> {
         // NEVER inject when an auto-suggestion is visible
         // This prevents accidentally submitting the auto-suggestion text
         if self.auto_suggestion_visible.load(Ordering::SeqCst) {
-            return false;
+            return None;
         }
 
         // Check explicit idle flag
         if self.is_idle.load(Ordering::SeqCst) {
-            return true;
+            return Some(*self.idle_method.lock().unwrap());
         }
 
         // Check silence timeout
@@ -117,7 +235,101 @@ impl Injector {
         let now = current_timestamp_ms();
         let silence_ms = now.saturating_sub(last_output);
 
-        silence_ms >= self.config.idle_timeout_ms
+        (silence_ms >= self.config.idle_timeout_ms).then_some(IdleMethod::Silence)
+    }
+
+    /// Whether an auto-suggestion is on screen (injection is blocked until it clears)
+    pub fn auto_suggestion_visible(&self) -> bool {
+        self.auto_suggestion_visible.load(Ordering::SeqCst)
+    }
+
+    /// The review desk, in review mode
+    pub fn review_desk(&self) -> Option<&Arc<ReviewDesk>> {
+        self.review.as_ref()
+    }
+
+    /// The message awaiting review, if any
+    pub fn awaiting_review(&self) -> Option<QueuedMessage> {
+        self.review.as_ref().and_then(|desk| desk.pending())
+    }
+
+    /// Record a keystroke from the human at the terminal
+    pub fn record_user_input(&self) {
+        self.last_input_ms
+            .store(current_timestamp_ms(), Ordering::SeqCst);
+    }
+
+    /// Set whether a human terminal is attached (for `pause_when_attached`)
+    pub fn set_human_attached(&self, attached: bool) {
+        self.human_attached.store(attached, Ordering::SeqCst);
+    }
+
+    /// Whether the human typed within the last `user_quiet_ms`
+    pub fn user_active(&self) -> bool {
+        let last_input = self.last_input_ms.load(Ordering::SeqCst);
+        last_input != 0
+            && current_timestamp_ms().saturating_sub(last_input) < self.config.user_quiet_ms
+    }
+
+    /// Pause or resume injection (messages keep queueing while paused)
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Whether injection is paused by the operator
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Why injection is on hold before a message is even dequeued, if it is.
+    /// Holding here keeps the message queued, so it can still be cancelled.
+    fn hold_reason(&self) -> Option<&'static str> {
+        if self.is_paused() {
+            Some("paused by operator")
+        } else if self.config.pause_when_attached && self.human_attached.load(Ordering::SeqCst) {
+            Some("human attached")
+        } else {
+            None
+        }
+    }
+
+    /// Why injection must wait for the human, if it must
+    fn user_hold(&self) -> Option<&'static str> {
+        self.hold_reason().or_else(|| {
+            if self.user_active() {
+                Some("user typing")
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Wait up to `WINDOW_TIMEOUT_MS` for the agent to go idle
+    async fn wait_for_window(&self, msg: &QueuedMessage) {
+        let deadline = Instant::now() + Duration::from_millis(WINDOW_TIMEOUT_MS);
+        while !self.check_idle() {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Injection window timeout for message {}, proceeding anyway",
+                    msg.id
+                );
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        info!("Agent is idle, proceeding with injection");
+    }
+
+    /// Wait until injecting can't splice into something the human is typing
+    async fn wait_for_user(&self, msg: &QueuedMessage) {
+        let mut logged = false;
+        while let Some(reason) = self.user_hold() {
+            if !logged {
+                info!("Deferring message {} ({})", msg.id, reason);
+                logged = true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
     }
 
     /// Get milliseconds since last output
@@ -144,30 +356,108 @@ impl Injector {
     }
 
     /// Run the injection loop
-    pub async fn run(&self) -> Result<()> {
+    pub async fn run(self: Arc<Self>) -> Result<()> {
         info!("Injection loop started");
 
         loop {
+            // Don't take a message off the queue while injection is on hold
+            let mut logged = false;
+            while let Some(reason) = self.hold_reason() {
+                if !logged {
+                    info!("Injection on hold ({})", reason);
+                    logged = true;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+
             // Wait for a message
             let msg = self.queue.wait_and_dequeue().await;
+
+            // Injection went on hold while we waited: put the message back so
+            // it stays cancellable, and wait above
+            if let Some(reason) = self.hold_reason() {
+                debug!("Returning message {} to the queue ({})", msg.id, reason);
+                self.queue.requeue(msg).await;
+                continue;
+            }
             debug!("Processing message: {}", msg.id);
 
+            // Review mode: new messages need approval (retries were already approved)
+            let msg = match self.review {
+                Some(ref desk) if msg.retries == 0 => match desk.review(msg.clone()).await {
+                    Some(ReviewDecision::Approve) => msg,
+                    Some(ReviewDecision::Edit(body)) => QueuedMessage { body, ..msg },
+                    Some(ReviewDecision::Reject(reason)) => {
+                        info!("Message {} rejected by reviewer: {}", msg.id, reason);
+                        self.queue.report_result(
+                            msg.id.clone(),
+                            InjectStatus::Failed,
+                            Some(format!("Rejected by reviewer: {}", reason)),
+                        );
+                        continue;
+                    }
+                    // Cancelled while awaiting review (the cancel reported it)
+                    None => continue,
+                },
+                _ => msg,
+            };
+
+            // Review can take a while: the message may have expired meanwhile
+            if msg.is_expired() {
+                info!("Message {} expired before injection, dropping", msg.id);
+                self.queue.report_result(
+                    msg.id.clone(),
+                    InjectStatus::Expired,
+                    Some("Expired before injection".to_string()),
+                );
+                continue;
+            }
+
+            // Urgent messages interrupt a busy agent instead of waiting for it.
+            // Only once: a retry waits like any other message.
+            if msg.interrupt && msg.retries == 0 && !self.check_idle() {
+                if let Err(e) = self.interrupt(&msg).await {
+                    error!("Interrupt error for {}: {}", msg.id, e);
+                    self.queue.report_result(
+                        msg.id.clone(),
+                        InjectStatus::Failed,
+                        Some(e.to_string()),
+                    );
+                    continue;
+                }
+            }
+
             // Report injecting status
             self.queue
                 .report_result(msg.id.clone(), InjectStatus::Injecting, None);
 
             // Try to inject
             match self.inject_message(&msg).await {
-                Ok(true) => {
-                    info!("Message {} delivered successfully", msg.id);
+                Ok(delivery @ (Delivery::Delivered | Delivery::Unverified)) => {
                     // Track injection time for auto-Enter detection
                     self.last_injection_ms
                         .store(current_timestamp_ms(), Ordering::SeqCst);
+                    let note = if delivery == Delivery::Unverified {
+                        warn!("Message {} delivered (unverified)", msg.id);
+                        Some("Delivered (unverified): no echo or agent activity seen".to_string())
+                    } else {
+                        info!("Message {} delivered successfully", msg.id);
+                        None
+                    };
                     self.queue
-                        .report_result(msg.id.clone(), InjectStatus::Delivered, None);
+                        .report_result(msg.id.clone(), InjectStatus::Delivered, note);
+
+                    // In its own task, so the queue keeps moving while the agent replies
+                    if let Some(sync) = msg.sync.as_ref().filter(|sync| sync.blocking) {
+                        let timeout_ms = sync.timeout_ms.unwrap_or(DEFAULT_CAPTURE_TIMEOUT_MS);
+                        let injector = Arc::clone(&self);
+                        tokio::spawn(async move { injector.capture_reply(&msg, timeout_ms).await });
+                    }
                 }
-                Ok(false) => {
-                    // Verification failed, retry
+                Ok(Delivery::Lost) => {
+                    // Drop the capture this attempt started
+                    self.take_capture(&msg.id).await;
+                    // Nothing reached the agent, retry
                     if msg.retries < self.config.max_retries {
                         warn!(
                             "Message {} not verified, retrying ({}/{})",
@@ -179,6 +469,7 @@ impl Injector {
                         self.queue.retry(msg).await;
                     } else {
                         error!("Message {} failed after {} retries", msg.id, msg.retries);
+                        self.remove_spill(&msg.id).await;
                         self.queue.report_result(
                             msg.id.clone(),
                             InjectStatus::Failed,
@@ -188,6 +479,8 @@ impl Injector {
                 }
                 Err(e) => {
                     error!("Injection error for {}: {}", msg.id, e);
+                    self.take_capture(&msg.id).await;
+                    self.remove_spill(&msg.id).await;
                     self.queue.report_result(
                         msg.id.clone(),
                         InjectStatus::Failed,
@@ -198,80 +491,510 @@ impl Injector {
         }
     }
 
-    /// Inject a single message
-    async fn inject_message(&self, msg: &QueuedMessage) -> Result<bool> {
-        info!("=== INJECT START: {} from {} ===", msg.id, msg.from);
+    /// Send the profile's interrupt key and wait for the prompt to come back
+    async fn interrupt(&self, msg: &QueuedMessage) -> Result<()> {
+        // Don't throw away what the human is typing
+        self.wait_for_user(msg).await;
+        if self.check_idle() {
+            return Ok(());
+        }
 
-        // Wait for injection window
-        let window_timeout = Duration::from_secs(10);
-        let start = Instant::now();
+        let profile = self.config.profile;
+        info!(
+            "Interrupting agent for message {} (sending {:?})",
+            msg.id, profile.interrupt
+        );
+        self.queue
+            .report_result(msg.id.clone(), InjectStatus::Interrupting, None);
+        self.pty_tx
+            .send(profile.interrupt.to_vec())
+            .await
+            .map_err(|_| anyhow::anyhow!("PTY channel closed"))?;
 
-        while start.elapsed() < window_timeout {
-            if self.check_idle() {
-                info!("Agent is idle, proceeding with injection");
+        let deadline = Instant::now() + Duration::from_millis(INTERRUPT_TIMEOUT_MS);
+        while !self.check_idle() {
+            if Instant::now() >= deadline {
+                warn!(
+                    "No prompt within {}ms of interrupting for message {}",
+                    INTERRUPT_TIMEOUT_MS, msg.id
+                );
                 break;
             }
             tokio::time::sleep(Duration::from_millis(50)).await;
         }
+        Ok(())
+    }
 
-        if !self.check_idle() {
-            warn!(
-                "Injection window timeout for message {}, proceeding anyway",
-                msg.id
-            );
+    /// Inject a single message
+    async fn inject_message(&self, msg: &QueuedMessage) -> Result<Delivery> {
+        info!("=== INJECT START: {} from {} ===", msg.id, msg.from);
+
+        // Wait for the injection window: the agent idle and the human quiet,
+        // both at once. Never splice into a line the human is typing. No
+        // timeout for that: the message waits in the injecting state until
+        // the user goes quiet, then the agent gets its window again, since
+        // what the user typed may have set it working.
+        loop {
+            self.wait_for_window(msg).await;
+            if self.user_hold().is_none() {
+                break;
+            }
+            self.wait_for_user(msg).await;
         }
 
+        // A reply still being captured ends where this message starts
+        self.cut_capture(msg).await;
+
         // Clear recent output for verification
         {
             let mut recent = self.recent_output.lock().await;
             recent.clear();
         }
 
+        // Oversized bodies are saved to a file; the agent gets a pointer instead
+        let spilled;
+        let msg =
+            if self.config.max_inline_bytes > 0 && msg.body.len() > self.config.max_inline_bytes {
+                spilled = self.spill_to_file(msg).await?;
+                &spilled
+            } else {
+                msg
+            };
+
         // Format the message (without Enter key)
-        let formatted = msg.format_for_injection();
+        let formatted = msg.format_with(&self.config.template);
 
         info!(
             "Step 1: Writing message content ({} bytes): {}",
             formatted.len(),
-            &formatted[..formatted.len().min(100)]
+            prefix_chars(&formatted, 100)
         );
 
-        // Step 1: Write message content (no Enter)
-        self.pty_tx
-            .send(formatted.as_bytes().to_vec())
-            .await
-            .map_err(|_| anyhow::anyhow!("PTY channel closed"))?;
+        // Step 1: Write message content (no Enter).
+        // As a bracketed paste when the profile and CLI allow it, so embedded
+        // newlines aren't treated as submits; otherwise apply the profile's
+        // newline handling and type it.
+        let profile = self.config.profile;
+        let use_paste = match profile.paste {
+            PasteStyle::Auto => self.modes.bracketed_paste(),
+            PasteStyle::Bracketed => true,
+            PasteStyle::Plain => false,
+        };
+        let (content, formatted) = if use_paste {
+            debug!("Using bracketed paste for message {}", msg.id);
+            (bracketed(&formatted), formatted)
+        } else {
+            let typed = profile.newlines.apply(&formatted);
+            if typed != formatted {
+                warn!(
+                    "Message {} flattened to one line ({} hasn't enabled bracketed paste)",
+                    msg.id, profile.name
+                );
+            }
+            (typed.as_bytes().to_vec(), typed)
+        };
+        self.write_chunked(&content).await?;
 
-        info!("Step 2: Waiting 50ms...");
+        info!(
+            "Step 2: Waiting {}ms ({} profile)...",
+            profile.enter_delay_ms, profile.name
+        );
 
         // Step 2: Wait for CLI to process the input
-        // Reduced from 200ms to 50ms for faster message delivery.
-        // Most CLIs process input within 20-30ms; 50ms provides a safety margin.
-        tokio::time::sleep(Duration::from_millis(50)).await;
+        tokio::time::sleep(Duration::from_millis(profile.enter_delay_ms)).await;
+
+        info!("Step 3: Sending submit key {:?}", profile.submit);
+
+        // Remember where post-submit output starts (for busy detection)
+        let submit_mark = self.output_bytes.load(Ordering::SeqCst);
 
-        info!("Step 3: Sending Enter key (\\r)");
+        // Capture the reply from here on, if the sender wants it
+        if msg.sync.as_ref().is_some_and(|sync| sync.blocking) {
+            *self.capture.lock().await = Some(ReplyCapture {
+                id: msg.id.clone(),
+                output: String::new(),
+            });
+        }
+
+        // Step 3: Send the profile's submit key (usually \r = carriage return)
+        self.submit().await?;
+
+        info!("=== INJECT COMPLETE: {} ===", msg.id);
 
-        // Step 3: Send Enter key (\r = carriage return)
+        if self.config.verify_timeout_ms == 0 {
+            // Verification disabled - assume delivery after a successful PTY write
+            return Ok(Delivery::Delivered);
+        }
+
+        // Step 4: Wait for evidence that the agent received the message
+        if self.verify_delivery(msg, &formatted, submit_mark).await {
+            return Ok(Delivery::Delivered);
+        }
+
+        // Typing the message again would duplicate it unless nothing arrived.
+        // If it is still sitting in the input line, only the submit was lost.
+        if self.input_line_holds(msg) {
+            info!(
+                "Message {} is still in the input line, sending {:?} again",
+                msg.id, profile.submit
+            );
+            let submit_mark = self.output_bytes.load(Ordering::SeqCst);
+            self.submit().await?;
+            if self.verify_delivery(msg, &formatted, submit_mark).await {
+                return Ok(Delivery::Delivered);
+            }
+            return Ok(Delivery::Unverified);
+        }
+        if self.recent_output.lock().await.is_empty() {
+            return Ok(Delivery::Lost);
+        }
+        Ok(Delivery::Unverified)
+    }
+
+    /// Send the profile's submit key
+    async fn submit(&self) -> Result<()> {
         self.pty_tx
-            .send(vec![0x0d]) // \r
+            .send(self.config.profile.submit.to_vec())
             .await
             .map_err(|_| anyhow::anyhow!("PTY channel closed"))?;
 
         // Mark as not idle (we just sent input)
         self.is_idle.store(false, Ordering::SeqCst);
+        Ok(())
+    }
 
-        info!("=== INJECT COMPLETE: {} ===", msg.id);
+    /// Whether the start of the body is on screen around the cursor, i.e.
+    /// typed but not submitted
+    fn input_line_holds(&self, msg: &QueuedMessage) -> bool {
+        let Some(rows) = self
+            .screen
+            .as_ref()
+            .and_then(|screen| screen.cursor_rows(INPUT_LINE_ROWS))
+        else {
+            return false;
+        };
+        let body_key: String = alphanumeric_lowercase(&msg.body)
+            .chars()
+            .take(FUZZY_ECHO_CHARS)
+            .collect();
+        body_key.chars().count() >= FUZZY_ECHO_MIN_CHARS
+            && alphanumeric_lowercase(&rows.lines.concat()).contains(&body_key)
+    }
+
+    /// Write content to the PTY in chunks of `chunk_bytes`, pausing
+    /// `chunk_delay_ms` between them so TUIs don't drop fast input
+    async fn write_chunked(&self, content: &[u8]) -> Result<()> {
+        let chunks = split_chunks(content, self.config.chunk_bytes);
+        if chunks.len() > 1 {
+            debug!("Writing {} bytes in {} chunks", content.len(), chunks.len());
+        }
+
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            if i > 0 && self.config.chunk_delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(self.config.chunk_delay_ms)).await;
+            }
+            self.pty_tx
+                .send(chunk.to_vec())
+                .await
+                .map_err(|_| anyhow::anyhow!("PTY channel closed"))?;
+        }
+        Ok(())
+    }
+
+    /// Save the body to `spill_dir` and return a copy of the message whose
+    /// body points at the file
+    async fn spill_to_file(&self, msg: &QueuedMessage) -> Result<QueuedMessage> {
+        let dir = Path::new(&self.config.spill_dir);
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create message directory {:?}", dir))?;
+
+        let path = dir.join(spill_file_name(&msg.id));
+        tokio::fs::write(&path, &msg.body)
+            .await
+            .with_context(|| format!("Failed to save message body to {:?}", path))?;
+        self.spilled
+            .lock()
+            .await
+            .insert(msg.id.clone(), path.clone());
+        info!(
+            "Message {} is {} bytes, saved to {:?}",
+            msg.id,
+            msg.body.len(),
+            path
+        );
+
+        let mut pointer = msg.clone();
+        pointer.body = format!(
+            "Message too long to show inline ({} bytes). Read the full text from {}",
+            msg.body.len(),
+            path.display()
+        );
+        Ok(pointer)
+    }
+
+    /// Delete the file a failed message's body was spilled to
+    async fn remove_spill(&self, id: &str) {
+        let Some(path) = self.spilled.lock().await.remove(id) else {
+            return;
+        };
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            debug!("Failed to remove spilled body {:?}: {}", path, e);
+        }
+    }
+
+    /// Delete every spilled body, and the spill directory if that empties it
+    pub async fn remove_spilled(&self) {
+        let paths: Vec<PathBuf> = self
+            .spilled
+            .lock()
+            .await
+            .drain()
+            .map(|(_, path)| path)
+            .collect();
+        for path in paths {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                debug!("Failed to remove spilled body {:?}: {}", path, e);
+            }
+        }
+        let _ = tokio::fs::remove_dir(&self.config.spill_dir).await;
+    }
+
+    /// Wait until the agent has replied to a delivered message and gone idle
+    /// (or `timeout_ms` passes), then send the sender what it wrote. Runs in
+    /// its own task; a message injected meanwhile cuts the reply short.
+    async fn capture_reply(&self, msg: &QueuedMessage, timeout_ms: u64) {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+        let timed_out = loop {
+            let replied = {
+                let capture = self.capture.lock().await;
+                match capture.as_ref() {
+                    Some(capture) if capture.id == msg.id => self
+                        .reply_text(&capture.output)
+                        .chars()
+                        .any(char::is_alphanumeric),
+                    // Cut short by the next message, which sent it already
+                    _ => return,
+                }
+            };
+            if replied && self.check_idle() {
+                break false;
+            }
+            if Instant::now() >= deadline {
+                break true;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        let Some(capture) = self.take_capture(&msg.id).await else {
+            return;
+        };
+        let text = self.reply_text(&capture.output);
+        if timed_out {
+            warn!(
+                "Reply to message {} not finished within {}ms, sending {} bytes",
+                msg.id,
+                timeout_ms,
+                text.len()
+            );
+        } else {
+            info!(
+                "Captured reply to message {} ({} bytes)",
+                msg.id,
+                text.len()
+            );
+        }
+        self.queue.report_captured(msg.id.clone(), text, timed_out);
+    }
+
+    /// End a reply still being captured, because `next` is about to be
+    /// injected. What was captured so far is sent, marked as timed out.
+    async fn cut_capture(&self, next: &QueuedMessage) {
+        let Some(capture) = self.capture.lock().await.take() else {
+            return;
+        };
+        let text = self.reply_text(&capture.output);
+        warn!(
+            "Reply to message {} cut short by message {}, sending {} bytes",
+            capture.id,
+            next.id,
+            text.len()
+        );
+        self.queue.report_captured(capture.id, text, true);
+    }
+
+    /// Take the capture if it is for message `id`
+    async fn take_capture(&self, id: &str) -> Option<ReplyCapture> {
+        let mut capture = self.capture.lock().await;
+        if capture.as_ref().is_some_and(|capture| capture.id == id) {
+            capture.take()
+        } else {
+            None
+        }
+    }
+
+    /// The reply in captured output
+    fn reply_text(&self, output: &str) -> String {
+        captured_reply(output, &self.config.template, self.config.profile.prompts)
+    }
 
-        // Assume delivery after successful PTY write; many CLIs don't echo input.
-        Ok(true)
+    /// Watch recent output for an echo of the message or for the agent
+    /// starting work, for up to `verify_timeout_ms`.
+    async fn verify_delivery(
+        &self,
+        msg: &QueuedMessage,
+        formatted: &str,
+        submit_mark: usize,
+    ) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(self.config.verify_timeout_ms);
+
+        loop {
+            {
+                let recent = self.recent_output.lock().await;
+                let after_submit = self.output_bytes.load(Ordering::SeqCst) - submit_mark;
+                if let Some(evidence) =
+                    delivery_evidence(&recent, after_submit, msg, formatted, &self.config.template)
+                {
+                    info!("Message {} verified: {}", msg.id, evidence);
+                    return true;
+                }
+            }
+
+            if Instant::now() >= deadline {
+                warn!(
+                    "No echo or activity for message {} within {}ms",
+                    msg.id, self.config.verify_timeout_ms
+                );
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
     }
 }
 
-fn is_relay_echo(output: &str) -> bool {
-    output.lines().all(|line| {
+/// Look for evidence in the output that an injected message reached the agent.
+///
+/// `output` is what is kept of the output since the message was written; its
+/// last `after_submit` bytes arrived after the Enter key. Returns a short description
+/// of the evidence found, if any:
+/// - the `[short-id]` marker from the formatted message
+/// - the start of the formatted message
+/// - the start of the body, ignoring everything but letters and digits
+///   (survives line wrapping and cursor-movement redraws)
+/// - new non-echo text after Enter (the agent went busy)
+fn delivery_evidence(
+    output: &str,
+    after_submit: usize,
+    msg: &QueuedMessage,
+    formatted: &str,
+    template: &MessageTemplate,
+) -> Option<&'static str> {
+    let text = strip_ansi(output);
+
+    if text.contains(&format!("[{}]", short_id(&msg.id))) {
+        return Some("short-id marker echoed");
+    }
+
+    let prefix: String = formatted.chars().take(ECHO_PREFIX_CHARS).collect();
+    if !prefix.trim().is_empty() && text.contains(prefix.trim()) {
+        return Some("message prefix echoed");
+    }
+
+    let body_key: String = alphanumeric_lowercase(&msg.body)
+        .chars()
+        .take(FUZZY_ECHO_CHARS)
+        .collect();
+    if body_key.chars().count() >= FUZZY_ECHO_MIN_CHARS
+        && alphanumeric_lowercase(&text).contains(&body_key)
+    {
+        return Some("message body echoed (fuzzy)");
+    }
+
+    // Bare prompt redraws and echoes of relay messages don't count as activity
+    let mut submit_mark = output.len().saturating_sub(after_submit);
+    while !output.is_char_boundary(submit_mark) {
+        submit_mark += 1;
+    }
+    let after_submit = strip_ansi(&output[submit_mark..]);
+    let went_busy = after_submit.lines().any(|line| {
         let trimmed = line.trim();
-        trimmed.is_empty() || trimmed.starts_with("Relay message from ")
-    })
+        trimmed.chars().any(char::is_alphanumeric) && !template.is_echo_line(trimmed)
+    });
+    if went_busy {
+        return Some("agent became busy");
+    }
+
+    None
+}
+
+/// The agent's reply in captured output: ANSI-stripped, without echoes of
+/// relay messages (also after an input prompt) or blank lines at either end
+fn captured_reply(output: &str, template: &MessageTemplate, prompts: &[&str]) -> String {
+    let text = strip_ansi(output);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| {
+            let unprompted = prompts
+                .iter()
+                .find_map(|prompt| line.trim_start().strip_prefix(prompt))
+                .unwrap_or(line);
+            !template.is_echo_line(unprompted)
+        })
+        .collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+/// Split into chunks of at most `size` bytes without cutting a UTF-8
+/// character in half (0 = a single chunk)
+fn split_chunks(content: &[u8], size: usize) -> Vec<&[u8]> {
+    if size == 0 || content.len() <= size {
+        return vec![content];
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while rest.len() > size {
+        let mut end = size;
+        // Back up over UTF-8 continuation bytes (0b10xxxxxx)
+        while end > 0 && (rest[end] & 0xC0) == 0x80 {
+            end -= 1;
+        }
+        if end == 0 {
+            end = size;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// File name for a spilled body, with the message ID made path-safe
+fn spill_file_name(id: &str) -> String {
+    let safe: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.md", safe)
+}
+
+/// Keep only letters and digits, lowercased
+fn alphanumeric_lowercase(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
 }
 
 /// Detect if output is an auto-suggestion (ghost text).
@@ -302,6 +1025,7 @@ fn current_timestamp_ms() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::InjectResponse;
     use tokio::sync::{broadcast, mpsc};
 
     fn test_config(idle_timeout_ms: u64) -> Config {
@@ -311,11 +1035,16 @@ mod tests {
         }
     }
 
+    fn test_message(id: &str, body: &str) -> QueuedMessage {
+        QueuedMessage::new(id.to_string(), "Alice".to_string(), body.to_string(), 0)
+    }
+
     fn test_parse_result(is_idle: bool) -> ParseResult {
         ParseResult {
             commands: Vec::new(),
             continuity_commands: Vec::new(),
             is_idle,
+            idle_method: is_idle.then_some(IdleMethod::Buffer),
             ready_signal: false,
         }
     }
@@ -325,10 +1054,22 @@ mod tests {
         let (pty_tx, _pty_rx) = mpsc::channel(1);
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
-        let injector = Injector::new(pty_tx, queue, test_config(600000));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(600000),
+            Arc::new(TerminalModes::new()),
+        );
 
+        assert_eq!(injector.idle_method(), None);
         injector.update_from_parse(&test_parse_result(true));
         assert!(injector.check_idle());
+        assert_eq!(injector.idle_method(), Some(IdleMethod::Buffer));
+
+        let mut on_screen = test_parse_result(true);
+        on_screen.idle_method = Some(IdleMethod::Screen);
+        injector.update_from_parse(&on_screen);
+        assert_eq!(injector.idle_method(), Some(IdleMethod::Screen));
     }
 
     #[tokio::test]
@@ -336,7 +1077,12 @@ mod tests {
         let (pty_tx, _pty_rx) = mpsc::channel(1);
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
-        let injector = Injector::new(pty_tx, queue, test_config(600000));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(600000),
+            Arc::new(TerminalModes::new()),
+        );
 
         injector.update_from_parse(&test_parse_result(true));
         assert!(injector.check_idle());
@@ -350,7 +1096,12 @@ mod tests {
         let (pty_tx, _pty_rx) = mpsc::channel(1);
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
-        let injector = Injector::new(pty_tx, queue, test_config(600000));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(600000),
+            Arc::new(TerminalModes::new()),
+        );
 
         injector.update_from_parse(&test_parse_result(true));
         assert!(injector.check_idle());
@@ -366,16 +1117,23 @@ mod tests {
         let (pty_tx, _pty_rx) = mpsc::channel(1);
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
-        let injector = Injector::new(pty_tx, queue, test_config(0));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(0),
+            Arc::new(TerminalModes::new()),
+        );
 
         assert!(injector.check_idle());
+        assert_eq!(injector.idle_method(), Some(IdleMethod::Silence));
     }
 
     #[test]
     fn test_is_relay_echo() {
-        assert!(is_relay_echo("Relay message from Alice [abc]: Hi\n"));
-        assert!(is_relay_echo("\nRelay message from Bob [def]: Yo\n\n"));
-        assert!(!is_relay_echo("Some other output\n"));
+        let template = MessageTemplate::default();
+        assert!(template.is_echo("Relay message from Alice [abc]: Hi\n"));
+        assert!(template.is_echo("\nRelay message from Bob [def]: Yo\n\n"));
+        assert!(!template.is_echo("Some other output\n"));
     }
 
     #[test]
@@ -402,7 +1160,12 @@ mod tests {
         let (pty_tx, _pty_rx) = mpsc::channel(1);
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
-        let injector = Injector::new(pty_tx, queue, test_config(600000));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(600000),
+            Arc::new(TerminalModes::new()),
+        );
 
         injector.update_from_parse(&test_parse_result(true));
         assert!(injector.check_idle());
@@ -424,7 +1187,12 @@ mod tests {
         let (response_tx, _response_rx) = broadcast::channel(1);
         let queue = Arc::new(MessageQueue::new(1, response_tx));
         // Use long timeout so we test the explicit idle flag behavior
-        let injector = Injector::new(pty_tx, queue, test_config(600000));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(600000),
+            Arc::new(TerminalModes::new()),
+        );
 
         // Start idle via explicit flag
         injector.update_from_parse(&test_parse_result(true));
@@ -498,4 +1266,813 @@ mod tests {
             "line1\n\x1b[7mA\x1b[27m\x1b[2muto complete\x1b[22m\nline2"
         ));
     }
+
+    #[test]
+    fn test_delivery_evidence_echo() {
+        let msg = test_message("abc1234567", "Please review the auth module");
+        let template = MessageTemplate::default();
+        let formatted = msg.format_with(&template);
+
+        assert_eq!(
+            delivery_evidence(
+                "\x1b[1m> Relay message from Alice [abc1234]: Ple",
+                0,
+                &msg,
+                &formatted,
+                &template
+            ),
+            Some("short-id marker echoed")
+        );
+
+        // Wrapped across lines and redrawn with cursor movement
+        let wrapped = "> Please review the a\r\n\x1b[2Cuth module";
+        assert_eq!(
+            delivery_evidence(wrapped, 0, &msg, &formatted, &template),
+            Some("message body echoed (fuzzy)")
+        );
+
+        assert_eq!(delivery_evidence("", 0, &msg, &formatted, &template), None);
+        assert_eq!(
+            delivery_evidence("\x1b[2K\r> ", 0, &msg, &formatted, &template),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delivery_evidence_busy_after_submit() {
+        let msg = test_message("abc1234567", "Hi");
+        let template = MessageTemplate::default();
+        let formatted = msg.format_with(&template);
+        // Output before the mark is the CLI redrawing its input box - not evidence
+        let before = "\x1b[2K\r> ";
+        let output = format!("{}\r\n\x1b[33m* Thinking...\x1b[0m", before);
+
+        assert_eq!(
+            delivery_evidence(before, 0, &msg, &formatted, &template),
+            None
+        );
+        assert_eq!(
+            delivery_evidence(
+                &output,
+                output.len() - before.len(),
+                &msg,
+                &formatted,
+                &template
+            ),
+            Some("agent became busy")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_without_any_output_is_lost() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 100,
+            ..test_config(0)
+        };
+        let injector = Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()));
+
+        let delivery = injector
+            .inject_message(&test_message("msg-1", "Hello there, agent"))
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Lost);
+
+        // Message and Enter were still written
+        assert!(pty_rx.recv().await.is_some());
+        assert_eq!(pty_rx.recv().await.unwrap(), vec![0x0d]);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_verified_by_echo() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 2000,
+            ..test_config(0)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            queue,
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+
+        // Echo whatever is written, like a terminal would
+        let echo_injector = Arc::clone(&injector);
+        tokio::spawn(async move {
+            while let Some(data) = pty_rx.recv().await {
+                echo_injector
+                    .record_output(&String::from_utf8_lossy(&data))
+                    .await;
+            }
+        });
+
+        let delivery = injector
+            .inject_message(&test_message("msg-2", "Hello there, agent"))
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Delivered);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_long_echo_then_busy_is_delivered() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 500,
+            ..test_config(0)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            queue,
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+
+        // The echo of the body alone overflows the recent output buffer,
+        // then the agent starts working once Enter arrives
+        let cli_injector = Arc::clone(&injector);
+        tokio::spawn(async move {
+            while let Some(data) = pty_rx.recv().await {
+                if data == b"\r" {
+                    cli_injector.record_output("\r\n* Working...").await;
+                } else {
+                    cli_injector
+                        .record_output(&String::from_utf8_lossy(&data))
+                        .await;
+                }
+            }
+        });
+
+        let body: String = (0..500)
+            .map(|i| format!("line {:04} of the report ", i))
+            .collect();
+        assert!(body.len() > 10000);
+        let delivery = injector
+            .inject_message(&test_message("msg-9", &body))
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Delivered);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_unrecognised_output_is_unverified() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 100,
+            ..test_config(0)
+        };
+        let screen = Arc::new(Screen::new(24, 80));
+        let injector = Arc::new(
+            Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()))
+                .with_screen(Arc::clone(&screen)),
+        );
+
+        // The CLI only redraws its (empty) prompt
+        let redraw_injector = Arc::clone(&injector);
+        let writes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&writes);
+        tokio::spawn(async move {
+            while let Some(data) = pty_rx.recv().await {
+                screen.feed(b"\x1b[2J\x1b[H> ");
+                redraw_injector.record_output("\x1b[2K\r> ").await;
+                log.lock().unwrap().push(data);
+            }
+        });
+
+        let delivery = injector
+            .inject_message(&test_message("msg-3", "Hello there, agent"))
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Unverified);
+        // Typed once and submitted once
+        assert_eq!(writes.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_resubmits_message_left_in_input_line() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 100,
+            ..test_config(0)
+        };
+        let screen = Arc::new(Screen::new(24, 80));
+        let injector = Arc::new(
+            Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()))
+                .with_screen(Arc::clone(&screen)),
+        );
+
+        // A CLI that shows the paste collapsed in its input box and ignores
+        // the first Enter
+        let cli_injector = Arc::clone(&injector);
+        let writes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&writes);
+        tokio::spawn(async move {
+            while let Some(data) = pty_rx.recv().await {
+                let count = log.lock().unwrap().len();
+                if count == 0 {
+                    screen.feed(b"> Hello there, agent");
+                    cli_injector.record_output("> [pasted]").await;
+                } else if count == 2 {
+                    cli_injector.record_output("\r\nThinking...\r\n").await;
+                }
+                log.lock().unwrap().push(data);
+            }
+        });
+
+        let delivery = injector
+            .inject_message(&test_message("msg-4", "Hello there, agent"))
+            .await
+            .unwrap();
+        assert_eq!(delivery, Delivery::Delivered);
+        // The body was typed once; only the submit key was repeated
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 3);
+        assert_eq!(writes[1], vec![0x0d]);
+        assert_eq!(writes[2], vec![0x0d]);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_uses_bracketed_paste_when_enabled() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            ..test_config(0)
+        };
+        let modes = Arc::new(TerminalModes::new());
+        modes.observe(b"\x1b[?2004h");
+        let injector = Injector::new(pty_tx, queue, config, modes);
+
+        injector
+            .inject_message(&test_message("msg-3", "line one\nline two"))
+            .await
+            .unwrap();
+
+        let content = pty_rx.recv().await.unwrap();
+        assert!(content.starts_with(b"\x1b[200~Relay message from Alice"));
+        assert!(content.ends_with(b"line one\nline two\x1b[201~"));
+        assert_eq!(pty_rx.recv().await.unwrap(), vec![0x0d]);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_follows_profile() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            profile: &crate::profile::GEMINI,
+            ..test_config(0)
+        };
+        // Gemini has no bracketed paste here, so newlines are flattened
+        let injector = Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()));
+
+        injector
+            .inject_message(&test_message("msg-4", "line one\nline two"))
+            .await
+            .unwrap();
+
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.ends_with("line one line two"));
+        assert_eq!(pty_rx.recv().await.unwrap(), crate::profile::GEMINI.submit);
+
+        // Once it enables bracketed paste, line breaks survive
+        injector.modes.observe(b"\x1b[?2004h");
+        injector
+            .inject_message(&test_message("msg-5", "line one\nline two"))
+            .await
+            .unwrap();
+        let content = pty_rx.recv().await.unwrap();
+        assert!(content.ends_with(b"line one\nline two\x1b[201~"));
+    }
+
+    #[test]
+    fn test_split_chunks() {
+        assert_eq!(split_chunks(b"abcdef", 0), vec![&b"abcdef"[..]]);
+        assert_eq!(
+            split_chunks(b"abcdefg", 3),
+            vec![&b"abc"[..], &b"def"[..], &b"g"[..]]
+        );
+
+        // "é" is two bytes; never split inside it
+        let text = "aé".repeat(3);
+        let chunks = split_chunks(text.as_bytes(), 2);
+        assert!(chunks.iter().all(|c| std::str::from_utf8(c).is_ok()));
+        assert_eq!(chunks.concat(), text.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_writes_in_chunks() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(64);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            chunk_bytes: 100,
+            chunk_delay_ms: 1,
+            ..test_config(0)
+        };
+        let injector = Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()));
+
+        let body = "x".repeat(1000);
+        let msg = test_message("msg-5", &body);
+        injector.inject_message(&msg).await.unwrap();
+
+        let expected = msg.format_for_injection().into_bytes();
+        let mut written = Vec::new();
+        let mut writes = 0;
+        while written.len() < expected.len() {
+            let chunk = pty_rx.recv().await.unwrap();
+            assert!(chunk.len() <= 100);
+            written.extend(chunk);
+            writes += 1;
+        }
+        assert_eq!(written, expected);
+        assert_eq!(writes, expected.len().div_ceil(100));
+        assert_eq!(pty_rx.recv().await.unwrap(), vec![0x0d]);
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_spills_large_body_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            max_inline_bytes: 100,
+            spill_dir: dir.path().to_string_lossy().into_owned(),
+            ..test_config(0)
+        };
+        let injector = Injector::new(pty_tx, queue, config, Arc::new(TerminalModes::new()));
+
+        let body = "y".repeat(500);
+        injector
+            .inject_message(&test_message("msg/6", &body))
+            .await
+            .unwrap();
+
+        let path = dir.path().join("msg_6.md");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), body);
+
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.starts_with("Relay message from Alice [msg/6]: "));
+        assert!(content.contains("(500 bytes)"));
+        assert!(content.contains(&path.display().to_string()));
+        assert!(!content.contains(&body));
+
+        // Cleaned up on exit, directory and all
+        injector.remove_spilled().await;
+        assert!(!path.exists());
+        assert!(!dir.path().exists());
+    }
+
+    #[test]
+    fn test_user_active_after_input() {
+        let (pty_tx, _pty_rx) = mpsc::channel(1);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let injector = Injector::new(
+            pty_tx,
+            queue,
+            test_config(0),
+            Arc::new(TerminalModes::new()),
+        );
+
+        assert!(!injector.user_active());
+        injector.record_user_input();
+        assert!(injector.user_active());
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_waits_for_user_to_stop_typing() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            user_quiet_ms: 200,
+            ..test_config(0)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            queue,
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+
+        injector.record_user_input();
+        let started = Instant::now();
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move {
+                injector
+                    .inject_message(&test_message("msg-7", "Hi"))
+                    .await
+                    .unwrap()
+            })
+        };
+
+        // Keep typing for a while; nothing may be written meanwhile
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            injector.record_user_input();
+        }
+        assert!(pty_rx.try_recv().is_err());
+
+        assert_eq!(task.await.unwrap(), Delivery::Delivered);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert!(pty_rx.recv().await.unwrap().ends_with(b"Hi"));
+    }
+
+    #[tokio::test]
+    async fn test_inject_message_waits_for_agent_after_user_stops() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            user_quiet_ms: 100,
+            ..test_config(600_000)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            queue,
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        injector.update_from_parse(&test_parse_result(true));
+        injector.record_user_input();
+
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move {
+                injector
+                    .inject_message(&test_message("msg-8", "Hi"))
+                    .await
+                    .unwrap()
+            })
+        };
+
+        // What the user typed sets the agent working before they go quiet
+        injector.record_output("Thinking...").await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!injector.user_active());
+        assert!(pty_rx.try_recv().is_err());
+
+        injector.update_from_parse(&test_parse_result(true));
+        assert_eq!(task.await.unwrap(), Delivery::Delivered);
+        assert!(pty_rx.recv().await.unwrap().ends_with(b"Hi"));
+    }
+
+    #[tokio::test]
+    async fn test_pause_when_attached_holds_injection() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(1);
+        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            pause_when_attached: true,
+            ..test_config(0)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            queue,
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        injector.set_human_attached(true);
+
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move {
+                injector
+                    .inject_message(&test_message("msg-8", "Hi"))
+                    .await
+                    .unwrap()
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(pty_rx.try_recv().is_err());
+
+        injector.set_human_attached(false);
+        assert_eq!(task.await.unwrap(), Delivery::Delivered);
+        assert!(pty_rx.recv().await.unwrap().ends_with(b"Hi"));
+    }
+
+    #[tokio::test]
+    async fn test_pause_while_waiting_keeps_message_queued() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(4, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            ..test_config(0)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        let run = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move { injector.run().await })
+        };
+
+        // Paused while the loop is already waiting for a message
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        injector.set_paused(true);
+        assert!(queue.enqueue(test_message("msg-p", "Hold me")).await);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(pty_rx.try_recv().is_err());
+        assert_eq!(queue.len().await, 1);
+        assert!(queue.cancel("msg-p").await.is_ok());
+
+        run.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_rejected_review_as_failed() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, mut response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let desk = Arc::new(ReviewDesk::new());
+        let config = Config {
+            verify_timeout_ms: 0,
+            ..test_config(0)
+        };
+        let injector = Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        )
+        .with_review(Arc::clone(&desk));
+        let (_, mut reviews) = desk.subscribe();
+        let task = tokio::spawn(async move { Arc::new(injector).run().await });
+
+        queue.enqueue(test_message("msg-9", "rm -rf /")).await;
+        assert_eq!(reviews.recv().await.unwrap().id, "msg-9");
+        desk.decide("msg-9", ReviewDecision::Reject("unsafe".to_string()))
+            .unwrap();
+
+        queue.enqueue(test_message("msg-10", "Hi")).await;
+        assert_eq!(reviews.recv().await.unwrap().id, "msg-10");
+        desk.decide("msg-10", ReviewDecision::Edit("Hello".to_string()))
+            .unwrap();
+
+        let mut rejected = None;
+        while rejected.is_none() {
+            if let InjectResponse::InjectResult {
+                id, status, error, ..
+            } = response_rx.recv().await.unwrap()
+            {
+                if id == "msg-9" && status == InjectStatus::Failed {
+                    rejected = error;
+                }
+            }
+        }
+        assert_eq!(rejected.as_deref(), Some("Rejected by reviewer: unsafe"));
+
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.ends_with("[msg-10]: Hello"));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_expires_message_that_outlived_review() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, mut response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let desk = Arc::new(ReviewDesk::new());
+        let injector = Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            test_config(0),
+            Arc::new(TerminalModes::new()),
+        )
+        .with_review(Arc::clone(&desk));
+        let (_, mut reviews) = desk.subscribe();
+        let task = tokio::spawn(async move { Arc::new(injector).run().await });
+
+        let mut msg = test_message("msg-1", "Hi");
+        msg.expires_at = Some(Instant::now() + Duration::from_millis(50));
+        queue.enqueue(msg).await;
+        assert_eq!(reviews.recv().await.unwrap().id, "msg-1");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        desk.decide("msg-1", ReviewDecision::Approve).unwrap();
+
+        loop {
+            if let InjectResponse::InjectResult { status, .. } = response_rx.recv().await.unwrap() {
+                if status.is_final() {
+                    assert_eq!(status, InjectStatus::Expired);
+                    break;
+                }
+            }
+        }
+        assert!(pty_rx.try_recv().is_err());
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_captures_reply_until_idle() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, mut response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            ..test_config(300)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move { injector.run().await })
+        };
+
+        let mut msg = test_message("msg-11", "What is 6*7?");
+        msg.sync = Some(crate::protocol::SyncMeta {
+            blocking: true,
+            timeout_ms: Some(10_000),
+        });
+        queue.enqueue(msg).await;
+
+        // Content, then the submit key
+        pty_rx.recv().await.unwrap();
+        assert_eq!(pty_rx.recv().await.unwrap(), b"\r");
+
+        injector
+            .record_output("\r\n> Relay message from Alice [msg-11]: What is 6*7?\r\n")
+            .await;
+        injector.record_output("\x1b[1m42\x1b[0m\r\n\r\n").await;
+
+        loop {
+            if let InjectResponse::CapturedResponse {
+                id,
+                text,
+                timed_out,
+                ..
+            } = response_rx.recv().await.unwrap()
+            {
+                assert_eq!(id, "msg-11");
+                assert_eq!(text, "42");
+                assert!(!timed_out);
+                break;
+            }
+        }
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_next_message_cuts_reply_capture_short() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, mut response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            ..test_config(300)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move { injector.run().await })
+        };
+
+        let mut msg = test_message("msg-11", "What is 6*7?");
+        msg.sync = Some(crate::protocol::SyncMeta {
+            blocking: true,
+            timeout_ms: Some(600_000),
+        });
+        queue.enqueue(msg).await;
+        queue.enqueue(test_message("msg-12", "Hello")).await;
+
+        pty_rx.recv().await.unwrap();
+        assert_eq!(pty_rx.recv().await.unwrap(), b"\r");
+        // The agent goes idle without replying; the queue keeps moving
+        injector.update_from_parse(&test_parse_result(true));
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.ends_with("Hello"));
+
+        loop {
+            if let InjectResponse::CapturedResponse { id, timed_out, .. } =
+                response_rx.recv().await.unwrap()
+            {
+                assert_eq!(id, "msg-11");
+                assert!(timed_out);
+                break;
+            }
+        }
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_interrupts_busy_agent() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, mut response_rx) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            profile: &crate::profile::CLAUDE,
+            ..test_config(600_000)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move { injector.run().await })
+        };
+
+        injector.record_output("Working on the refactor...").await;
+        let mut msg = test_message("msg-12", "Stop, the build is broken");
+        msg.interrupt = true;
+        queue.enqueue(msg).await;
+
+        // The profile's interrupt key (Escape for Claude) goes first
+        assert_eq!(pty_rx.recv().await.unwrap(), b"\x1b");
+        // Then the prompt comes back
+        injector.update_from_parse(&test_parse_result(true));
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.ends_with("Stop, the build is broken"));
+
+        let mut statuses = Vec::new();
+        while statuses.last() != Some(&InjectStatus::Delivered) {
+            if let InjectResponse::InjectResult { status, .. } = response_rx.recv().await.unwrap() {
+                statuses.push(status);
+            }
+        }
+        assert_eq!(
+            statuses,
+            vec![
+                InjectStatus::Queued,
+                InjectStatus::Interrupting,
+                InjectStatus::Injecting,
+                InjectStatus::Delivered
+            ]
+        );
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_retry_does_not_interrupt_again() {
+        let (pty_tx, mut pty_rx) = mpsc::channel(16);
+        let (response_tx, _) = broadcast::channel(16);
+        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let config = Config {
+            verify_timeout_ms: 0,
+            profile: &crate::profile::CLAUDE,
+            ..test_config(600_000)
+        };
+        let injector = Arc::new(Injector::new(
+            pty_tx,
+            Arc::clone(&queue),
+            config,
+            Arc::new(TerminalModes::new()),
+        ));
+        let task = {
+            let injector = Arc::clone(&injector);
+            tokio::spawn(async move { injector.run().await })
+        };
+
+        injector.record_output("Working on the refactor...").await;
+        let mut msg = test_message("msg-13", "Stop, the build is broken");
+        msg.interrupt = true;
+        msg.retries = 1;
+        queue.enqueue(msg).await;
+
+        // Busy: the retry waits instead of sending the interrupt key
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(pty_rx.try_recv().is_err());
+
+        injector.update_from_parse(&test_parse_result(true));
+        let content = String::from_utf8(pty_rx.recv().await.unwrap()).unwrap();
+        assert!(content.ends_with("Stop, the build is broken"));
+        task.abort();
+    }
 }
@@ -0,0 +1,163 @@
+//! Key encoding for `send_keys` requests.
+//!
+//! Turns named keys (`enter`, `escape`, `up`, `ctrl-c`, `alt-b`, `f5`, ...)
+//! into the bytes a terminal sends for them. Cursor keys follow the child's
+//! DECCKM mode: `ESC O A` in application cursor mode, `ESC [ A` otherwise.
+//! Names are case-insensitive; a single character is sent as itself.
+
+use crate::protocol::KeyInput;
+use anyhow::{bail, Result};
+
+/// Encode keys and literal text as terminal input
+pub fn encode(inputs: &[KeyInput], application_cursor: bool) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for input in inputs {
+        match input {
+            KeyInput::Key { key } => out.extend(encode_key(key, application_cursor)?),
+            KeyInput::Text { text } => out.extend_from_slice(text.as_bytes()),
+        }
+    }
+    Ok(out)
+}
+
+/// Encode a single named key
+pub fn encode_key(name: &str, application_cursor: bool) -> Result<Vec<u8>> {
+    let trimmed = name.trim();
+    let mut chars = trimmed.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c.to_string().into_bytes());
+    }
+
+    let lower = trimmed.to_ascii_lowercase();
+    if let Some(rest) = strip_modifier(&lower, "ctrl") {
+        return match control_byte(rest) {
+            Some(byte) => Ok(vec![byte]),
+            None => bail!(
+                "Unknown key {:?} (ctrl- takes a letter or one of @[\\]^_?)",
+                name
+            ),
+        };
+    }
+    if let Some(rest) = strip_modifier(&lower, "alt") {
+        // Meta sends ESC before the key; keep the original case of a letter
+        let rest = &trimmed[trimmed.len() - rest.len()..];
+        let mut out = vec![0x1b];
+        out.extend(encode_key(rest, application_cursor)?);
+        return Ok(out);
+    }
+
+    let cursor = |final_byte: u8| {
+        let intro = if application_cursor { b'O' } else { b'[' };
+        vec![0x1b, intro, final_byte]
+    };
+
+    Ok(match lower.replace('_', "-").as_str() {
+        "enter" | "return" => b"\r".to_vec(),
+        "tab" => b"\t".to_vec(),
+        "backtab" | "shift-tab" => b"\x1b[Z".to_vec(),
+        "escape" | "esc" => vec![0x1b],
+        "backspace" => vec![0x7f],
+        "space" => b" ".to_vec(),
+        "up" => cursor(b'A'),
+        "down" => cursor(b'B'),
+        "right" => cursor(b'C'),
+        "left" => cursor(b'D'),
+        "home" => cursor(b'H'),
+        "end" => cursor(b'F'),
+        "insert" => b"\x1b[2~".to_vec(),
+        "delete" | "del" => b"\x1b[3~".to_vec(),
+        "page-up" | "pageup" => b"\x1b[5~".to_vec(),
+        "page-down" | "pagedown" => b"\x1b[6~".to_vec(),
+        "f1" => b"\x1bOP".to_vec(),
+        "f2" => b"\x1bOQ".to_vec(),
+        "f3" => b"\x1bOR".to_vec(),
+        "f4" => b"\x1bOS".to_vec(),
+        "f5" => b"\x1b[15~".to_vec(),
+        "f6" => b"\x1b[17~".to_vec(),
+        "f7" => b"\x1b[18~".to_vec(),
+        "f8" => b"\x1b[19~".to_vec(),
+        "f9" => b"\x1b[20~".to_vec(),
+        "f10" => b"\x1b[21~".to_vec(),
+        "f11" => b"\x1b[23~".to_vec(),
+        "f12" => b"\x1b[24~".to_vec(),
+        _ => bail!("Unknown key {:?}", name),
+    })
+}
+
+/// The key after a `ctrl-` / `alt-` style modifier (`-`, `+` or `_` separated)
+fn strip_modifier<'a>(name: &'a str, modifier: &str) -> Option<&'a str> {
+    let rest = name.strip_prefix(modifier)?;
+    let rest = rest.strip_prefix(['-', '+', '_'])?;
+    (!rest.is_empty()).then_some(rest)
+}
+
+/// The control character for ctrl + `key`
+fn control_byte(key: &str) -> Option<u8> {
+    match key.as_bytes() {
+        [c @ b'a'..=b'z'] => Some(c & 0x1f),
+        [b'@'] | b"space" => Some(0x00),
+        [b'['] => Some(0x1b),
+        [b'\\'] => Some(0x1c),
+        [b']'] => Some(0x1d),
+        [b'^'] => Some(0x1e),
+        [b'_'] => Some(0x1f),
+        [b'?'] => Some(0x7f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> KeyInput {
+        KeyInput::Key {
+            key: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_named_keys() {
+        assert_eq!(encode_key("Enter", false).unwrap(), b"\r");
+        assert_eq!(encode_key("ESC", false).unwrap(), b"\x1b");
+        assert_eq!(encode_key("shift_tab", false).unwrap(), b"\x1b[Z");
+        assert_eq!(encode_key("page-down", false).unwrap(), b"\x1b[6~");
+        assert_eq!(encode_key("f5", false).unwrap(), b"\x1b[15~");
+        assert_eq!(encode_key("Y", false).unwrap(), b"Y");
+        assert_eq!(encode_key("2", false).unwrap(), b"2");
+        assert!(encode_key("hyper-x", false).is_err());
+        assert!(encode_key("", false).is_err());
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(encode_key("ctrl-c", false).unwrap(), [0x03]);
+        assert_eq!(encode_key("Ctrl+D", false).unwrap(), [0x04]);
+        assert_eq!(encode_key("ctrl-[", false).unwrap(), [0x1b]);
+        assert!(encode_key("ctrl-1", false).is_err());
+        assert_eq!(encode_key("alt-B", false).unwrap(), b"\x1bB");
+        assert_eq!(encode_key("alt-left", false).unwrap(), b"\x1b\x1b[D");
+    }
+
+    #[test]
+    fn test_cursor_keys_follow_mode() {
+        assert_eq!(encode_key("up", false).unwrap(), b"\x1b[A");
+        assert_eq!(encode_key("up", true).unwrap(), b"\x1bOA");
+        assert_eq!(encode_key("home", true).unwrap(), b"\x1bOH");
+        // Not a cursor key, so unaffected
+        assert_eq!(encode_key("delete", true).unwrap(), b"\x1b[3~");
+    }
+
+    #[test]
+    fn test_encode_mixes_keys_and_text() {
+        let inputs = vec![
+            key("escape"),
+            KeyInput::Text {
+                text: "/clear".to_string(),
+            },
+            key("enter"),
+        ];
+        assert_eq!(encode(&inputs, false).unwrap(), b"\x1b/clear\r");
+        assert!(encode(&[key("nope")], false).is_err());
+    }
+}
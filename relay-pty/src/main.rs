@@ -5,33 +5,58 @@
 //!
 //! Usage:
 //!   relay-pty --name myagent -- claude --model opus
+//!   relay-pty attach --name myagent
 
 // Allow dead code - this binary has public API components that may not be used internally
 #![allow(dead_code)]
 
+mod attach;
+mod escape;
+mod events;
 mod inject;
+mod keys;
+mod modes;
 mod outbox_monitor;
+mod output;
 mod parser;
+mod profile;
 mod protocol;
 mod pty;
 mod queue;
+mod responder;
+mod review;
+mod screen;
 mod socket;
+mod statusline;
+mod template;
+mod tty;
 
 use anyhow::{Context, Result};
+use attach::{AttachContext, AttachControl, AttachServer};
 use clap::Parser;
+use escape::{EscapeCommand, EscapeParser, StdinAction};
+use events::EventLog;
 use inject::Injector;
+use modes::TerminalModes;
 use outbox_monitor::OutboxMonitor;
+use output::OutputHub;
 use parser::OutputParser;
-use protocol::Config;
+use profile::InjectionProfile;
+use protocol::{default_spill_dir, Config, RelayEvent};
 use pty::{AsyncPty, Pty};
 use queue::MessageQueue;
-use socket::{SocketServer, StatusInfo, StatusQuery};
+use responder::QueryResponder;
+use review::ReviewDesk;
+use screen::Screen;
+use socket::{recv_subscription, SocketContext, SocketServer, StatusInfo, StatusQuery};
+use statusline::StatusLine;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write as IoWrite};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use template::MessageTemplate;
 use tokio::io::AsyncWriteExt;
 use tokio::select;
 use tokio::signal::unix::{signal, SignalKind};
@@ -68,6 +93,10 @@ struct Args {
     #[arg(short, long)]
     socket: Option<String>,
 
+    /// Socket for `relay-pty attach` when headless (default: the socket path with .attach.sock)
+    #[arg(long)]
+    attach_socket: Option<String>,
+
     /// Regex pattern to detect agent prompt
     #[arg(long, default_value = r"^[>$%#] $")]
     prompt_pattern: String,
@@ -85,6 +114,10 @@ struct Args {
     #[arg(long)]
     json_output: bool,
 
+    /// Maximum unacknowledged relay events kept for replay to socket event subscribers
+    #[arg(long, default_value_t = events::DEFAULT_EVENT_BUFFER)]
+    event_buffer: usize,
+
     /// Maximum injection retries
     #[arg(long, default_value = "3")]
     max_retries: u32,
@@ -93,6 +126,65 @@ struct Args {
     #[arg(long, default_value = "300")]
     retry_delay: u64,
 
+    /// Milliseconds to watch for an echo or agent activity after injecting before retrying
+    /// Set to 0 to assume delivery as soon as the message is written.
+    #[arg(long, default_value = "3000")]
+    verify_timeout: u64,
+
+    /// Injection profile (claude, codex, gemini, opencode, generic)
+    /// Defaults to auto-detection from the wrapped command.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Template for injected messages. Placeholders: {from}, {id}, {short_id},
+    /// {thread}, {priority}, {retries}, {body} (required).
+    /// Defaults to the profile's template.
+    #[arg(long)]
+    message_template: Option<String>,
+
+    /// Prefix added to a message on its first retry
+    #[arg(long, default_value = template::DEFAULT_RETRY_PREFIX)]
+    retry_prefix: String,
+
+    /// Prefix added to a message on its second and later retries
+    #[arg(long, default_value = template::DEFAULT_URGENT_PREFIX)]
+    urgent_prefix: String,
+
+    /// Write injected content in chunks of at most this many bytes (0 = single write)
+    #[arg(long, default_value = "1024")]
+    chunk_size: usize,
+
+    /// Milliseconds to pause between chunks
+    #[arg(long, default_value = "10")]
+    chunk_delay: u64,
+
+    /// Bodies larger than this many bytes are saved to a file and the agent is sent a
+    /// pointer to it instead (0 = always inject inline)
+    #[arg(long, default_value = "32768")]
+    max_inline_bytes: usize,
+
+    /// Directory for oversized message bodies, deleted on exit
+    /// (default: .relay-pty/messages/{name} in the working directory)
+    #[arg(long)]
+    spill_dir: Option<String>,
+
+    /// Milliseconds the user must stop typing before a message is injected (0 = don't wait)
+    #[arg(long, default_value = "2000")]
+    user_quiet: u64,
+
+    /// Hold all injection while a human terminal is attached
+    #[arg(long)]
+    pause_when_attached: bool,
+
+    /// Show relay status (idle/busy, queue, last delivery) on the bottom row of the terminal
+    #[arg(long)]
+    status_line: bool,
+
+    /// Hold each new message until a human approves, edits or rejects it
+    /// (at the terminal with Ctrl-] a/m/x, or over the socket as a reviewer)
+    #[arg(long)]
+    review: bool,
+
     /// Log level (error, warn, info, debug, trace)
     #[arg(long, default_value = "info")]
     log_level: String,
@@ -109,6 +201,10 @@ struct Args {
     #[arg(long)]
     log_file: Option<String>,
 
+    /// Bytes of recent output kept for socket subscribers that ask for a replay
+    #[arg(long, default_value_t = output::DEFAULT_SCROLLBACK_BYTES)]
+    scrollback_bytes: usize,
+
     /// Outbox directory for file-based relay messages (default: /tmp/relay/{WORKSPACE_ID}/outbox/{name} when set)
     #[arg(long)]
     outbox: Option<String>,
@@ -142,8 +238,71 @@ struct Args {
     command: Vec<String>,
 }
 
+/// Attach this terminal to a headless relay-pty
+#[derive(Parser, Debug)]
+#[command(name = "relay-pty attach")]
+#[command(about = "Attach this terminal to a headless relay-pty (detach with the detach key)")]
+struct AttachArgs {
+    /// Agent name/identifier
+    #[arg(short, long, required_unless_present_any = ["socket", "attach_socket"])]
+    name: Option<String>,
+
+    /// Unix socket path of the relay-pty (the attach socket is derived from it)
+    #[arg(short, long)]
+    socket: Option<String>,
+
+    /// Attach socket path (overrides --name and --socket)
+    #[arg(long, conflicts_with = "read_only")]
+    attach_socket: Option<String>,
+
+    /// Key that detaches, leaving the agent running
+    #[arg(long, default_value = attach::DEFAULT_DETACH_KEY)]
+    detach_key: String,
+
+    /// Watch as a read-only observer over the relay socket (works alongside an attached
+    /// client, and for agents in a terminal)
+    #[arg(long)]
+    read_only: bool,
+}
+
+/// Default socket path for an agent
+fn default_socket_path(name: &str, workspace_id: Option<&str>) -> String {
+    match workspace_id {
+        Some(workspace_id) => format!("/tmp/relay/{}/sockets/{}.sock", workspace_id, name),
+        None => format!("/tmp/relay-pty-{}.sock", name),
+    }
+}
+
+/// The WORKSPACE_ID environment variable, if set
+fn workspace_id() -> Option<String> {
+    std::env::var("WORKSPACE_ID")
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// `relay-pty attach`: connect this terminal to a headless session
+async fn run_attach(args: AttachArgs) -> Result<()> {
+    let detach_key = attach::parse_detach_key(&args.detach_key)?;
+    let socket = args.socket.unwrap_or_else(|| {
+        let name = args.name.as_deref().unwrap_or_default();
+        default_socket_path(name, workspace_id().as_deref())
+    });
+    let socket_path = if args.read_only {
+        socket
+    } else {
+        args.attach_socket
+            .unwrap_or_else(|| attach::default_socket_path(&socket))
+    };
+    attach::run_client(&socket_path, detach_key, args.read_only).await
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("attach") {
+        return run_attach(AttachArgs::parse_from(std::env::args().skip(1))).await;
+    }
+
     let args = Args::parse();
 
     // Initialize logging
@@ -160,18 +319,14 @@ async fn main() -> Result<()> {
     info!("Command: {:?}", args.command);
 
     // Build configuration
-    let workspace_id = std::env::var("WORKSPACE_ID")
-        .ok()
-        .map(|id| id.trim().to_string())
-        .filter(|id| !id.is_empty());
+    let workspace_id = workspace_id();
 
-    let socket_path = args.socket.unwrap_or_else(|| {
-        if let Some(ref workspace_id) = workspace_id {
-            format!("/tmp/relay/{}/sockets/{}.sock", workspace_id, args.name)
-        } else {
-            format!("/tmp/relay-pty-{}.sock", args.name)
-        }
-    });
+    let socket_path = args
+        .socket
+        .unwrap_or_else(|| default_socket_path(&args.name, workspace_id.as_deref()));
+    let attach_socket_path = args
+        .attach_socket
+        .unwrap_or_else(|| attach::default_socket_path(&socket_path));
 
     let outbox_path = args.outbox.or_else(|| {
         workspace_id
@@ -179,6 +334,27 @@ async fn main() -> Result<()> {
             .map(|id| format!("/tmp/relay/{}/outbox/{}", id, args.name))
     });
 
+    let spill_dir = args
+        .spill_dir
+        .unwrap_or_else(|| default_spill_dir(&args.name));
+
+    let profile = match args.profile.as_deref() {
+        Some(name) => InjectionProfile::by_name(name).with_context(|| {
+            format!(
+                "Unknown profile '{}' (expected one of: {})",
+                name,
+                InjectionProfile::names()
+            )
+        })?,
+        None => InjectionProfile::detect(&args.command),
+    };
+    info!("Injection profile: {}", profile.name);
+
+    let template_format = args.message_template.as_deref().unwrap_or(profile.template);
+    let template = MessageTemplate::parse(template_format)
+        .context("Invalid --message-template")?
+        .with_prefixes(args.retry_prefix, args.urgent_prefix);
+
     let config = Config {
         name: args.name.clone(),
         socket_path: socket_path.clone(),
@@ -189,6 +365,15 @@ async fn main() -> Result<()> {
         command: args.command.clone(),
         max_retries: args.max_retries,
         retry_delay_ms: args.retry_delay,
+        verify_timeout_ms: args.verify_timeout,
+        profile,
+        template,
+        chunk_bytes: args.chunk_size,
+        chunk_delay_ms: args.chunk_delay,
+        max_inline_bytes: args.max_inline_bytes,
+        spill_dir,
+        user_quiet_ms: args.user_quiet,
+        pause_when_attached: args.pause_when_attached,
     };
 
     info!("Socket: {}", socket_path);
@@ -216,10 +401,25 @@ async fn main() -> Result<()> {
     };
 
     // Create PTY and spawn agent
-    let pty = Pty::spawn(&args.command, args.rows, args.cols).context("Failed to spawn agent")?;
+    // The status line takes the bottom row, so the child gets one row fewer
+    let mut status_line = if args.status_line {
+        tty::terminal_size().map(|(rows, cols)| StatusLine::new(rows, cols))
+    } else {
+        None
+    };
+    let (rows, cols) = match status_line {
+        Some(ref line) => (Some(line.child_rows()), Some(line.cols())),
+        None => (args.rows, args.cols),
+    };
+
+    let pty = Pty::spawn(&args.command, rows, cols).context("Failed to spawn agent")?;
+
+    // Headless screen model of what the child has drawn
+    let (screen_rows, screen_cols) = pty.size().unwrap_or((24, 80));
+    let screen = Arc::new(Screen::new(screen_rows, screen_cols));
 
     // Set raw mode for transparent terminal passthrough (if TTY available)
-    let is_interactive = Pty::set_raw_mode().context("Failed to set raw mode")?;
+    let is_interactive = tty::set_raw_mode().context("Failed to set raw mode")?;
     if is_interactive {
         info!("Running in interactive mode (TTY)");
     } else {
@@ -244,12 +444,40 @@ async fn main() -> Result<()> {
         args.cleanup_interval,
     ));
 
+    // PTY output fan-out for socket subscribers
+    let output_hub = Arc::new(OutputHub::new(args.scrollback_bytes));
+
+    // Sequenced relay events for socket subscribers (replayed until acked)
+    let event_log = Arc::new(EventLog::new(args.event_buffer));
+
+    // Terminal modes requested by the child (bracketed paste, cursor keys)
+    let terminal_modes = Arc::new(TerminalModes::new());
+
+    // Answers terminal queries (cursor position, device attributes, ...) when
+    // no real terminal is attached to do it
+    let mut responder = QueryResponder::new(
+        Arc::clone(&screen),
+        Arc::clone(&terminal_modes),
+        !is_interactive,
+    );
+
+    // Review mode: each new message waits for a human decision
+    let review_desk = args.review.then(|| Arc::new(ReviewDesk::new()));
+
     // Create injector (clone inject_tx since we also need it for SocketServer)
-    let injector = Arc::new(Injector::new(
+    let mut injector = Injector::new(
         inject_tx.clone(),
         Arc::clone(&queue),
         config.clone(),
-    ));
+        Arc::clone(&terminal_modes),
+    )
+    .with_screen(Arc::clone(&screen));
+    if let Some(ref desk) = review_desk {
+        info!("Review mode: messages wait for approval");
+        injector = injector.with_review(Arc::clone(desk));
+    }
+    let injector = Arc::new(injector);
+    injector.set_human_attached(is_interactive);
 
     // Create output parser
     let mut parser = if let Some(ref outbox) = outbox_path {
@@ -262,9 +490,11 @@ async fn main() -> Result<()> {
         }
         info!("File-based relay enabled, outbox: {}", outbox);
         OutputParser::with_outbox(config.name.clone(), &config.prompt_pattern, outbox_path)
+            .with_prompts(profile.prompts)
     } else {
-        OutputParser::new(config.name.clone(), &config.prompt_pattern)
-    };
+        OutputParser::new(config.name.clone(), &config.prompt_pattern).with_prompts(profile.prompts)
+    }
+    .with_screen(Arc::clone(&screen));
 
     // Create outbox monitor for stale file detection
     let mut outbox_monitor: Option<OutboxMonitor> = if let Some(ref outbox) = outbox_path {
@@ -298,13 +528,26 @@ async fn main() -> Result<()> {
     // Interval for checking stale outbox files
     let mut stale_check_interval = tokio::time::interval(std::time::Duration::from_secs(10));
 
+    // Interval for refreshing the status line
+    let mut status_line_interval = tokio::time::interval(Duration::from_millis(500));
+    // Whether the last output chunk ended where the status line can be drawn
+    let mut output_ends_cleanly = true;
+
     // Start socket server
     let socket_server = SocketServer::new(
         socket_path.clone(),
-        Arc::clone(&queue),
-        status_tx,
-        shutdown_tx,
-        inject_tx.clone(), // For SendEnter requests
+        SocketContext {
+            agent_name: config.name.clone(),
+            queue: Arc::clone(&queue),
+            status_tx,
+            shutdown_tx,
+            pty_tx: inject_tx.clone(), // For SendEnter requests
+            output: Arc::clone(&output_hub),
+            events: Arc::clone(&event_log),
+            review: review_desk.clone(),
+            modes: Arc::clone(&terminal_modes),
+            screen: Arc::clone(&screen),
+        },
     );
 
     let socket_handle = tokio::spawn(async move {
@@ -313,6 +556,25 @@ async fn main() -> Result<()> {
         }
     });
 
+    // Headless sessions can be attached to from another terminal
+    let (attach_tx, mut attach_rx) = mpsc::channel::<AttachControl>(64);
+    let attach_handle = (!is_interactive).then(|| {
+        let attach_server = AttachServer::new(
+            attach_socket_path.clone(),
+            AttachContext {
+                screen: Arc::clone(&screen),
+                modes: Arc::clone(&terminal_modes),
+                output: Arc::clone(&output_hub),
+                control_tx: attach_tx,
+            },
+        );
+        tokio::spawn(async move {
+            if let Err(e) = attach_server.run().await {
+                error!("Attach server error: {}", e);
+            }
+        })
+    });
+
     // Start injector
     let injector_clone = Arc::clone(&injector);
     let injector_handle = tokio::spawn(async move {
@@ -346,7 +608,19 @@ async fn main() -> Result<()> {
 
     // Main event loop
     let json_output = config.json_output;
+    let echo_template = config.template.clone();
     let mut stdout = tokio::io::stdout();
+    if let Some(line) = status_line.as_ref().filter(|line| line.is_shown()) {
+        stdout.write_all(&line.setup()).await?;
+        stdout.flush().await?;
+    }
+    // Ctrl-] commands are only for a human at a terminal
+    let mut escape_parser = is_interactive.then(EscapeParser::new);
+    // Messages awaiting review are announced on the terminal
+    let mut review_rx = match review_desk {
+        Some(ref desk) if is_interactive => Some(desk.subscribe().1),
+        _ => None,
+    };
 
     // Track MCP approval state to prevent duplicate approvals
     let mcp_approved = AtomicBool::new(false);
@@ -429,16 +703,90 @@ async fn main() -> Result<()> {
             // Handle SIGWINCH (terminal resize)
             _ = sigwinch.recv() => {
                 debug!("SIGWINCH received");
-                if let Some((rows, cols)) = get_terminal_size() {
-                    let _ = async_pty.resize(rows, cols);
+                if let Some((rows, cols)) = tty::terminal_size() {
+                    let mut child_rows = rows;
+                    if let Some(ref mut line) = status_line {
+                        let was_shown = line.is_shown();
+                        // Too small to give up a row: hide it until it grows again
+                        if !line.resize(rows, cols) && was_shown {
+                            stdout.write_all(&line.teardown(&screen)).await?;
+                            stdout.flush().await?;
+                        }
+                        child_rows = line.child_rows();
+                    }
+                    let _ = async_pty.resize(child_rows, cols);
+                    screen.resize(child_rows, cols);
                 }
             }
 
             // Handle stdin (user input)
             Some(data) = stdin_rx.recv() => {
                 debug!("Received {} bytes from stdin", data.len());
-                if let Err(e) = async_pty.send(data).await {
-                    error!("Failed to send to PTY: {}", e);
+                let actions = match escape_parser {
+                    Some(ref mut parser) => parser.feed(&data),
+                    None => vec![StdinAction::Forward(data)],
+                };
+                for action in actions {
+                    match action {
+                        StdinAction::Forward(data) => {
+                            injector.record_user_input();
+                            if let Err(e) = async_pty.send(data).await {
+                                error!("Failed to send to PTY: {}", e);
+                            }
+                        }
+                        StdinAction::Echo(bytes) => {
+                            stdout.write_all(&bytes).await?;
+                            stdout.flush().await?;
+                        }
+                        StdinAction::Line(line) => {
+                            let lines = escape::finish_edit(&injector, line);
+                            stdout.write_all(&escape::render(&lines)).await?;
+                            stdout.flush().await?;
+                        }
+                        StdinAction::Command(cmd) => {
+                            debug!("Escape command: {:?}", cmd);
+                            if cmd == EscapeCommand::EditReview && injector.awaiting_review().is_none() {
+                                if let Some(ref mut parser) = escape_parser {
+                                    parser.cancel_line();
+                                }
+                            }
+                            if cmd == EscapeCommand::SendEnter {
+                                if let Err(e) = async_pty.send(profile.submit.to_vec()).await {
+                                    error!("Failed to send to PTY: {}", e);
+                                }
+                            }
+                            let lines = escape::execute(cmd, &injector, &queue).await;
+                            stdout.write_all(&escape::render(&lines)).await?;
+                            stdout.flush().await?;
+                        }
+                    }
+                }
+            }
+
+            // Handle a terminal attached over the attach socket
+            Some(control) = attach_rx.recv() => {
+                match control {
+                    AttachControl::Attached { rows, cols } => {
+                        // The attached terminal answers queries and owns the size now
+                        injector.set_human_attached(true);
+                        responder.set_active(false);
+                        // The attach server already sized the screen model for its repaint
+                        let _ = async_pty.resize(rows, cols);
+                    }
+                    AttachControl::Resize { rows, cols } => {
+                        let _ = async_pty.resize(rows, cols);
+                        screen.resize(rows, cols);
+                    }
+                    AttachControl::Input(data) => {
+                        injector.record_user_input();
+                        if let Err(e) = async_pty.send(data).await {
+                            error!("Failed to send to PTY: {}", e);
+                        }
+                    }
+                    AttachControl::Detached => {
+                        injector.set_human_attached(false);
+                        responder.set_active(true);
+                    }
                 }
             }
 
@@ -452,18 +800,7 @@ async fn main() -> Result<()> {
             // Handle PTY output
             result = async_pty.recv() => {
                 if let Some(data) = result {
-                    // Check for cursor position query (CSI 6n) and respond
-                    // Codex CLI sends this query and waits for response - without it, Codex times out
-                    // Pattern: ESC [ 6 n or ESC [ ? 6 n
                     let text = String::from_utf8_lossy(&data);
-                    if text.contains("\x1b[6n") || text.contains("\x1b[?6n") {
-                        debug!("Detected cursor position query (CSI 6n), sending response");
-                        // Respond with cursor at position (1, 1): ESC [ 1 ; 1 R
-                        let response = b"\x1b[1;1R";
-                        if let Err(e) = async_pty.send(response.to_vec()).await {
-                            warn!("Failed to send cursor position response: {}", e);
-                        }
-                    }
 
                     // Auto-approve MCP servers for Claude/Cursor CLI
                     // Shows approval prompt on first run - auto-send 'a' to approve all
@@ -623,10 +960,7 @@ async fn main() -> Result<()> {
                     // This means the agent is working and not stuck
                     // Skip reset for relay message echoes (those don't indicate the agent is working)
                     let clean_text = strip_ansi(&text);
-                    let is_relay_echo = clean_text.lines().all(|line| {
-                        let trimmed = line.trim();
-                        trimmed.is_empty() || trimmed.starts_with("Relay message from ")
-                    });
+                    let is_relay_echo = echo_template.is_echo(&clean_text);
                     if !is_relay_echo && clean_text.len() > 10 {
                         // Meaningful output - agent is working, reset retry count
                         if auto_enter_retry_count > 0 {
@@ -639,6 +973,12 @@ async fn main() -> Result<()> {
                     stdout.write_all(&data).await?;
                     stdout.flush().await?;
 
+                    // The child may have drawn over the status line
+                    if let Some(ref mut line) = status_line {
+                        line.invalidate();
+                        output_ends_cleanly = statusline::ends_cleanly(&data);
+                    }
+
                     // Write to log file if configured
                     if let Some(ref log) = log_file {
                         let mut file = log.lock().await;
@@ -646,6 +986,21 @@ async fn main() -> Result<()> {
                         let _ = file.flush();
                     }
 
+                    // Stream to socket subscribers
+                    output_hub.publish(&data);
+
+                    // Track mode changes (e.g. bracketed paste) before anything is injected
+                    terminal_modes.observe(&data);
+
+                    // Update the screen model and answer terminal queries (headless only)
+                    // Codex CLI sends CSI 6n and waits for the response - without it, Codex times out
+                    let reply = responder.observe(&data);
+                    if !reply.is_empty() {
+                        if let Err(e) = async_pty.send(reply).await {
+                            warn!("Failed to answer terminal query: {}", e);
+                        }
+                    }
+
                     // Parse output
                     let parse_result = parser.process(&data);
 
@@ -655,15 +1010,23 @@ async fn main() -> Result<()> {
 
                     // Output parsed commands as JSON if enabled
                     if json_output {
-                        for cmd in parse_result.commands {
-                            let json = serde_json::to_string(&cmd)?;
+                        for cmd in &parse_result.commands {
+                            let json = serde_json::to_string(cmd)?;
                             eprintln!("{}", json);
                         }
-                        for cmd in parse_result.continuity_commands {
-                            let json = serde_json::to_string(&cmd)?;
+                        for cmd in &parse_result.continuity_commands {
+                            let json = serde_json::to_string(cmd)?;
                             eprintln!("{}", json);
                         }
                     }
+
+                    // Deliver parsed commands to socket event subscribers
+                    for cmd in parse_result.commands {
+                        event_log.push(RelayEvent::Command(cmd));
+                    }
+                    for cmd in parse_result.continuity_commands {
+                        event_log.push(RelayEvent::Continuity(cmd));
+                    }
                 } else {
                     // PTY closed
                     info!("PTY closed");
@@ -673,15 +1036,43 @@ async fn main() -> Result<()> {
 
             // Handle status queries
             Some(query) = status_rx.recv() => {
+                let stats = queue.stats().await;
                 let info = StatusInfo {
                     agent_idle: injector.check_idle(),
-                    queue_length: queue.len().await,
-                    cursor_position: None, // Would need terminal query
+                    idle_method: injector.idle_method(),
+                    paused: injector.is_paused(),
+                    user_active: injector.user_active(),
+                    queue_length: stats.queue_length,
+                    ready: stats.ready,
+                    scheduled: stats.scheduled,
+                    max_size: stats.max_size,
+                    seen_count: stats.seen_count,
+                    cursor_position: Some(screen.cursor_position()),
                     last_output_ms: injector.silence_ms(),
                 };
                 let _ = query.response_tx.send(info);
             }
 
+            // Announce messages awaiting review to the human at the terminal
+            result = recv_subscription(&mut review_rx) => {
+                if let Ok(msg) = result {
+                    let lines = escape::review_prompt(&msg);
+                    stdout.write_all(&escape::render(&lines)).await?;
+                    stdout.flush().await?;
+                }
+            }
+
+            // Redraw the status line if it changed or was drawn over
+            _ = status_line_interval.tick(), if status_line.is_some() => {
+                let text = statusline::describe(&config.name, &injector, &queue).await;
+                if let Some(ref mut line) = status_line {
+                    if line.is_shown() && output_ends_cleanly && line.is_stale(&text) {
+                        stdout.write_all(&line.draw(&text, &screen)).await?;
+                        stdout.flush().await?;
+                    }
+                }
+            }
+
             // Check for stale outbox files periodically
             _ = stale_check_interval.tick() => {
                 if let Some(ref mut monitor) = outbox_monitor {
@@ -692,6 +1083,7 @@ async fn main() -> Result<()> {
                         if let Ok(json) = serde_json::to_string(&stale) {
                             eprintln!("{}", json);
                         }
+                        event_log.push(RelayEvent::StaleOutbox(stale));
                     }
                 }
             }
@@ -797,11 +1189,24 @@ async fn main() -> Result<()> {
     // Terminate child and reap
     let _ = async_pty.shutdown();
 
+    // The agent is gone: nobody will read spilled message bodies now
+    injector.remove_spilled().await;
+
+    // Release the status line row
+    if let Some(line) = status_line.as_ref().filter(|line| line.is_shown()) {
+        let _ = stdout.write_all(&line.teardown(&screen)).await;
+        let _ = stdout.flush().await;
+    }
+
     // Restore terminal
-    Pty::restore_terminal();
+    tty::restore_terminal();
 
-    // Clean up socket
+    // Clean up sockets
     let _ = std::fs::remove_file(&socket_path);
+    if let Some(handle) = attach_handle {
+        handle.abort();
+        let _ = std::fs::remove_file(&attach_socket_path);
+    }
 
     // Abort background tasks
     socket_handle.abort();
@@ -811,27 +1216,6 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Get current terminal size
-fn get_terminal_size() -> Option<(u16, u16)> {
-    use nix::libc;
-    use nix::pty::Winsize;
-
-    let mut winsize = Winsize {
-        ws_row: 0,
-        ws_col: 0,
-        ws_xpixel: 0,
-        ws_ypixel: 0,
-    };
-
-    unsafe {
-        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut winsize) == 0 {
-            Some((winsize.ws_row, winsize.ws_col))
-        } else {
-            None
-        }
-    }
-}
-
 /// Detect if the agent is in an editor mode (vim INSERT, nano, etc.)
 /// When in editor mode, auto-Enter should be suppressed to avoid corrupting the editor state.
 ///
@@ -0,0 +1,183 @@
+//! Terminal modes requested by the child process.
+//!
+//! Watches the PTY output stream for DEC private mode set/reset sequences
+//! (`CSI ? Pn h` / `CSI ? Pn l`) so injection and key encoding can adapt to
+//! what the CLI supports. Sequences split across reads are handled by
+//! keeping the scanner state between chunks.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::debug;
+
+/// DEC private mode number for application cursor keys (DECCKM)
+const MODE_APPLICATION_CURSOR: u32 = 1;
+
+/// DEC private mode number for bracketed paste
+const MODE_BRACKETED_PASTE: u32 = 2004;
+
+/// Longest parameter list we buffer before giving up on a sequence
+const MAX_PARAM_BYTES: usize = 32;
+
+/// Start of a bracketed paste
+pub const PASTE_START: &[u8] = b"\x1b[200~";
+
+/// End of a bracketed paste
+pub const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Modes the child has currently enabled
+#[derive(Default)]
+pub struct TerminalModes {
+    /// CSI ?2004h - the child wants pastes wrapped in ESC[200~ ... ESC[201~
+    bracketed_paste: AtomicBool,
+    /// CSI ?1h - the child wants cursor keys sent as ESC O A rather than ESC [ A
+    application_cursor: AtomicBool,
+    /// Scanner state carried between output chunks
+    scanner: Mutex<Scanner>,
+}
+
+#[derive(Debug, Default)]
+struct Scanner {
+    state: ScanState,
+    params: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    /// Saw ESC
+    Escape,
+    /// Saw ESC [
+    Csi,
+    /// Inside ESC [ ? ... collecting parameters
+    PrivateCsi,
+}
+
+impl TerminalModes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan a chunk of child output for mode changes
+    pub fn observe(&self, data: &[u8]) {
+        let mut scanner = self.scanner.lock().unwrap();
+
+        for &byte in data {
+            scanner.state = match (scanner.state, byte) {
+                (_, 0x1b) => ScanState::Escape,
+                (ScanState::Escape, b'[') => ScanState::Csi,
+                (ScanState::Csi, b'?') => {
+                    scanner.params.clear();
+                    ScanState::PrivateCsi
+                }
+                (ScanState::PrivateCsi, b'0'..=b'9' | b';')
+                    if scanner.params.len() < MAX_PARAM_BYTES =>
+                {
+                    scanner.params.push(byte);
+                    ScanState::PrivateCsi
+                }
+                (ScanState::PrivateCsi, b'h' | b'l') => {
+                    self.apply(&scanner.params, byte == b'h');
+                    ScanState::Ground
+                }
+                _ => ScanState::Ground,
+            };
+        }
+    }
+
+    /// Whether the child has bracketed paste enabled
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste.load(Ordering::SeqCst)
+    }
+
+    /// Whether the child has application cursor keys enabled
+    pub fn application_cursor(&self) -> bool {
+        self.application_cursor.load(Ordering::SeqCst)
+    }
+
+    fn apply(&self, params: &[u8], enabled: bool) {
+        let params = String::from_utf8_lossy(params);
+        for mode in params.split(';').filter_map(|p| p.parse::<u32>().ok()) {
+            let (flag, name) = match mode {
+                MODE_BRACKETED_PASTE => (&self.bracketed_paste, "Bracketed paste"),
+                MODE_APPLICATION_CURSOR => (&self.application_cursor, "Application cursor keys"),
+                _ => continue,
+            };
+            let was = flag.swap(enabled, Ordering::SeqCst);
+            if was != enabled {
+                debug!("{} {}", name, if enabled { "enabled" } else { "disabled" });
+            }
+        }
+    }
+}
+
+/// Wrap text in bracketed paste markers.
+/// Any end marker inside the text is removed so the paste can't be cut short.
+pub fn bracketed(text: &str) -> Vec<u8> {
+    let end = std::str::from_utf8(PASTE_END).unwrap();
+    let sanitized = text.replace(end, "");
+
+    let mut out = Vec::with_capacity(sanitized.len() + PASTE_START.len() + PASTE_END.len());
+    out.extend_from_slice(PASTE_START);
+    out.extend_from_slice(sanitized.as_bytes());
+    out.extend_from_slice(PASTE_END);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_bracketed_paste() {
+        let modes = TerminalModes::new();
+        assert!(!modes.bracketed_paste());
+
+        modes.observe(b"\x1b[?2004h> ");
+        assert!(modes.bracketed_paste());
+
+        modes.observe(b"bye\x1b[?2004l");
+        assert!(!modes.bracketed_paste());
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let modes = TerminalModes::new();
+        modes.observe(b"output\x1b[?20");
+        assert!(!modes.bracketed_paste());
+        modes.observe(b"04h");
+        assert!(modes.bracketed_paste());
+    }
+
+    #[test]
+    fn test_multiple_params_and_unrelated_modes() {
+        let modes = TerminalModes::new();
+        modes.observe(b"\x1b[?1049;2004h");
+        assert!(modes.bracketed_paste());
+
+        modes.observe(b"\x1b[?25l\x1b[2004l");
+        assert!(modes.bracketed_paste());
+    }
+
+    #[test]
+    fn test_tracks_application_cursor() {
+        let modes = TerminalModes::new();
+        modes.observe(b"\x1b[?1h\x1b=");
+        assert!(modes.application_cursor());
+        assert!(!modes.bracketed_paste());
+
+        // ?12 (cursor blink) must not be mistaken for ?1
+        modes.observe(b"\x1b[?12l");
+        assert!(modes.application_cursor());
+        modes.observe(b"\x1b[?1l");
+        assert!(!modes.application_cursor());
+    }
+
+    #[test]
+    fn test_bracketed_strips_end_marker() {
+        assert_eq!(
+            bracketed("a\nb\x1b[201~c"),
+            b"\x1b[200~a\nbc\x1b[201~".to_vec()
+        );
+    }
+}
@@ -189,11 +189,9 @@ impl OutboxMonitor {
                         }
                     });
                 }
-                EventKind::Remove(_) => {
-                    // File was deleted (processed) - stop tracking
-                    if tracked.remove(&filename).is_some() {
-                        debug!("Outbox file processed and removed: {}", filename);
-                    }
+                // File was deleted (processed) - stop tracking
+                EventKind::Remove(_) if tracked.remove(&filename).is_some() => {
+                    debug!("Outbox file processed and removed: {}", filename);
                 }
                 _ => {}
             }
@@ -0,0 +1,200 @@
+//! PTY output fan-out for socket subscribers.
+//!
+//! Keeps a bounded scrollback of recent raw output and broadcasts every new
+//! chunk to subscribers. Subscribing and replaying the scrollback happen under
+//! the same lock as publishing, so a client starting from "the last N bytes"
+//! never sees a gap or a duplicate between the replay and the live stream.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Default number of raw output bytes kept for replay
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 64 * 1024;
+
+/// Number of chunks a subscriber may fall behind before it starts lagging
+const OUTPUT_CHANNEL_CAPACITY: usize = 256;
+
+/// Broadcast hub for PTY output
+pub struct OutputHub {
+    /// Live output chunks
+    tx: broadcast::Sender<Arc<[u8]>>,
+    /// Most recent raw output, bounded by `capacity`
+    scrollback: Mutex<VecDeque<u8>>,
+    /// Maximum scrollback size in bytes
+    capacity: usize,
+}
+
+impl OutputHub {
+    /// Create a hub keeping up to `scrollback_bytes` of output for replay
+    pub fn new(scrollback_bytes: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(OUTPUT_CHANNEL_CAPACITY);
+        Self {
+            tx,
+            scrollback: Mutex::new(VecDeque::with_capacity(scrollback_bytes)),
+            capacity: scrollback_bytes,
+        }
+    }
+
+    /// Record a chunk of PTY output and send it to all subscribers
+    pub fn publish(&self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let mut scrollback = self.scrollback.lock().unwrap();
+        scrollback.extend(data.iter().copied());
+        if scrollback.len() > self.capacity {
+            let excess = scrollback.len() - self.capacity;
+            scrollback.drain(..excess);
+        }
+
+        // Sending while holding the scrollback lock keeps replay + live gap-free.
+        // An error only means there are no subscribers right now.
+        let _ = self.tx.send(Arc::from(data));
+    }
+
+    /// Subscribe to live output, returning up to `replay_bytes` of scrollback
+    /// that precedes the first live chunk.
+    pub fn subscribe(&self, replay_bytes: usize) -> (Vec<u8>, broadcast::Receiver<Arc<[u8]>>) {
+        let scrollback = self.scrollback.lock().unwrap();
+        let rx = self.tx.subscribe();
+
+        let start = scrollback.len().saturating_sub(replay_bytes);
+        let mut replay: Vec<u8> = scrollback.range(start..).copied().collect();
+
+        // Don't start the replay in the middle of a multi-byte UTF-8 character
+        let skip = replay
+            .iter()
+            .take_while(|b| (0x80..0xC0).contains(*b))
+            .count();
+        replay.drain(..skip);
+
+        (replay, rx)
+    }
+
+    /// Number of bytes currently held in the scrollback
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.lock().unwrap().len()
+    }
+}
+
+/// Incremental UTF-8 decoder for chunked output.
+///
+/// PTY reads can split a multi-byte character across chunks; this holds the
+/// incomplete tail back until the next chunk arrives instead of emitting
+/// replacement characters.
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the next chunk, returning all complete characters
+    pub fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+
+        let mut out = String::with_capacity(self.pending.len());
+        let mut pos = 0;
+
+        loop {
+            match std::str::from_utf8(&self.pending[pos..]) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    pos = self.pending.len();
+                    break;
+                }
+                Err(e) => {
+                    let valid_up_to = pos + e.valid_up_to();
+                    // Already validated, so this never substitutes anything
+                    out.push_str(&String::from_utf8_lossy(&self.pending[pos..valid_up_to]));
+                    match e.error_len() {
+                        Some(len) => {
+                            // Invalid sequence - replace it and keep going
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            pos = valid_up_to + len;
+                        }
+                        None => {
+                            // Incomplete character at the end - wait for more bytes
+                            pos = valid_up_to;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pending.drain(..pos);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_from_now_skips_scrollback() {
+        let hub = OutputHub::new(1024);
+        hub.publish(b"before");
+
+        let (replay, mut rx) = hub.subscribe(0);
+        assert!(replay.is_empty());
+
+        hub.publish(b"after");
+        assert_eq!(&*rx.recv().await.unwrap(), b"after");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replays_last_bytes() {
+        let hub = OutputHub::new(1024);
+        hub.publish(b"hello ");
+        hub.publish(b"world");
+
+        let (replay, _rx) = hub.subscribe(5);
+        assert_eq!(replay, b"world");
+
+        let (replay, _rx) = hub.subscribe(10_000);
+        assert_eq!(replay, b"hello world");
+    }
+
+    #[test]
+    fn test_scrollback_is_bounded() {
+        let hub = OutputHub::new(8);
+        hub.publish(b"0123456789");
+        assert_eq!(hub.scrollback_len(), 8);
+
+        let (replay, _rx) = hub.subscribe(100);
+        assert_eq!(replay, b"23456789");
+    }
+
+    #[test]
+    fn test_replay_skips_partial_utf8_character() {
+        let hub = OutputHub::new(1024);
+        hub.publish("a世界".as_bytes());
+
+        // 4 bytes back lands inside '世'
+        let (replay, _rx) = hub.subscribe(4);
+        assert_eq!(String::from_utf8(replay).unwrap(), "界");
+    }
+
+    #[test]
+    fn test_utf8_decoder_handles_split_characters() {
+        let bytes = "世界".as_bytes();
+        let mut decoder = Utf8Decoder::new();
+
+        assert_eq!(decoder.decode(&bytes[..2]), "");
+        assert_eq!(decoder.decode(&bytes[2..4]), "世");
+        assert_eq!(decoder.decode(&bytes[4..]), "界");
+    }
+
+    #[test]
+    fn test_utf8_decoder_replaces_invalid_bytes() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"a\xffb"), "a\u{FFFD}b");
+    }
+}
@@ -4,13 +4,16 @@
 //! - `<<<RELAY_JSON>>>...<<<END_RELAY>>>` structured JSON format (preferred)
 //! - `->relay:` commands (messages, broadcasts, spawns) - legacy format
 //! - `KIND: continuity` file-based messages via `->relay-file:ID`
-//! - Prompt patterns (to detect idle state)
+//! - Prompt patterns (to detect idle state), on the rendered screen near the
+//!   cursor when a screen model is attached, else on the last line of output
 //! - `->pty:ready` explicit ready signal
 
-use crate::protocol::{ContinuityCommand, ParsedRelayCommand};
+use crate::profile;
+use crate::protocol::{prefix_chars, ContinuityCommand, IdleMethod, ParsedRelayCommand};
+use crate::screen::Screen;
 use regex::Regex;
 use serde::Deserialize;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use tracing::{debug, info, warn};
 
 /// Find the nearest character boundary at or before the given byte index.
@@ -236,6 +239,9 @@ fn thread_pattern() -> &'static Regex {
     THREAD_PATTERN.get_or_init(|| Regex::new(r"\[thread:([^\]]+)\]").unwrap())
 }
 
+/// Rows above and below the cursor searched for a prompt on the screen
+const SCREEN_PROMPT_ROWS: usize = 2;
+
 fn ansi_pattern() -> &'static Regex {
     ANSI_PATTERN.get_or_init(|| Regex::new(r"\x1B\[[0-9;]*[A-Za-z]|\x1B\].*?\x07").unwrap())
 }
@@ -252,6 +258,10 @@ pub struct OutputParser {
     last_parsed_pos: usize,
     /// Outbox directory for file-based messages (optional)
     outbox_path: Option<std::path::PathBuf>,
+    /// Prompt suffixes recognized in addition to `prompt_pattern`
+    common_prompts: &'static [&'static str],
+    /// Rendered screen, searched for the prompt before the buffer
+    screen: Option<Arc<Screen>>,
 }
 
 impl OutputParser {
@@ -266,6 +276,8 @@ impl OutputParser {
             buffer: String::new(),
             last_parsed_pos: 0,
             outbox_path: None,
+            common_prompts: profile::GENERIC.prompts,
+            screen: None,
         }
     }
 
@@ -284,9 +296,24 @@ impl OutputParser {
             buffer: String::new(),
             last_parsed_pos: 0,
             outbox_path: Some(outbox_path),
+            common_prompts: profile::GENERIC.prompts,
+            screen: None,
         }
     }
 
+    /// Recognize the given prompt suffixes (from the injection profile)
+    /// instead of the generic set
+    pub fn with_prompts(mut self, prompts: &'static [&'static str]) -> Self {
+        self.common_prompts = prompts;
+        self
+    }
+
+    /// Look for the prompt on this screen (fed the same output) first
+    pub fn with_screen(mut self, screen: Arc<Screen>) -> Self {
+        self.screen = Some(screen);
+        self
+    }
+
     /// Process new output and return any parsed commands
     pub fn process(&mut self, output: &[u8]) -> ParseResult {
         // Convert to string, handling invalid UTF-8
@@ -325,7 +352,7 @@ impl OutputParser {
         }
 
         // Check for prompt
-        let is_idle = self.check_for_prompt();
+        let prompt = self.check_for_prompt();
 
         // Check for explicit ready signal
         let ready_signal = self.buffer.contains("->pty:ready");
@@ -334,10 +361,17 @@ impl OutputParser {
             self.buffer = self.buffer.replace("->pty:ready", "");
         }
 
+        let idle_method = if ready_signal {
+            Some(IdleMethod::ReadySignal)
+        } else {
+            prompt
+        };
+
         ParseResult {
             commands: parse_output.commands,
             continuity_commands: parse_output.continuity_commands,
-            is_idle: is_idle || ready_signal,
+            is_idle: idle_method.is_some(),
+            idle_method,
             ready_signal,
         }
     }
@@ -448,11 +482,8 @@ impl OutputParser {
                 let cmd = match msg.kind.as_str() {
                     "spawn" => {
                         if let (Some(name), Some(cli)) = (&msg.name, &msg.cli) {
-                            let task_preview = msg
-                                .body
-                                .as_ref()
-                                .map(|b| &b[..b.len().min(50)])
-                                .unwrap_or("");
+                            let task_preview =
+                                msg.body.as_ref().map(|b| prefix_chars(b, 50)).unwrap_or("");
                             info!(
                                 "SPAWN PARSED: {} spawning {} with {} (task: {}...)",
                                 self.agent_name, name, cli, task_preview
@@ -669,34 +700,60 @@ impl OutputParser {
         }
     }
 
-    /// Check if the buffer ends with a prompt pattern
-    fn check_for_prompt(&self) -> bool {
+    /// Check for a prompt, returning how it was found.
+    /// The screen decides once anything is drawn; the buffer is the fallback.
+    fn check_for_prompt(&self) -> Option<IdleMethod> {
+        if let Some(found) = self.screen.as_ref().and_then(|s| self.check_screen(s)) {
+            return found.then_some(IdleMethod::Screen);
+        }
+
         // Get last few lines of buffer
-        let lines: Vec<&str> = self.buffer.lines().collect();
-        if let Some(last_line) = lines.last() {
-            if self.prompt_pattern.is_match(last_line) {
-                return true;
-            }
+        let last_line = self.buffer.lines().last()?;
+        self.is_prompt(last_line).then_some(IdleMethod::Buffer)
+    }
+
+    /// Whether the cursor sits after a prompt, or an empty prompt is drawn on
+    /// a row near it (TUIs often park the cursor elsewhere).
+    /// None while the screen is blank.
+    fn check_screen(&self, screen: &Screen) -> Option<bool> {
+        let rows = screen.cursor_rows(SCREEN_PROMPT_ROWS)?;
+        if self.is_prompt(strip_border(&rows.before_cursor)) {
+            return Some(true);
         }
+        Some(rows.lines.iter().any(|line| {
+            let line = strip_border(line).trim_end_matches(BORDER_CHARS);
+            !line.is_empty() && self.is_bare_prompt(line)
+        }))
+    }
 
-        // Also check common prompt patterns
-        let common_prompts = [
-            "> ",      // Claude
-            "$ ",      // Shell
-            ">>> ",    // Gemini
-            "codex> ", // Codex
-        ];
-
-        if let Some(last_line) = lines.last() {
-            let trimmed = last_line.trim_start();
-            for prompt in common_prompts {
-                if trimmed.ends_with(prompt) {
-                    return true;
-                }
-            }
+    /// Whether a row (border and trailing blanks removed) is nothing but an
+    /// empty prompt. Unlike [`Self::is_prompt`], text merely ending in `>` or `$`
+    /// doesn't count.
+    fn is_bare_prompt(&self, row: &str) -> bool {
+        // Trailing blanks are indistinguishable on screen; give the prompt its space back
+        let line = format!("{} ", row);
+        let whole_row = self
+            .prompt_pattern
+            .find(&line)
+            .is_some_and(|m| m.start() == 0 && m.end() == line.len());
+        whole_row
+            || self
+                .common_prompts
+                .iter()
+                .any(|prompt| row == prompt.trim_end())
+    }
+
+    /// Whether a line ends with the prompt pattern or a profile prompt
+    fn is_prompt(&self, line: &str) -> bool {
+        if self.prompt_pattern.is_match(line) {
+            return true;
         }
 
-        false
+        // Also check the profile's prompt suffixes
+        let trimmed = line.trim_start();
+        self.common_prompts
+            .iter()
+            .any(|prompt| trimmed.ends_with(prompt))
     }
 
     /// Clear the buffer
@@ -730,6 +787,8 @@ pub struct ParseResult {
     pub continuity_commands: Vec<ContinuityCommand>,
     /// Whether agent appears idle
     pub is_idle: bool,
+    /// How the idle state was detected (None when not idle)
+    pub idle_method: Option<IdleMethod>,
     /// Whether explicit ready signal was received
     pub ready_signal: bool,
 }
@@ -740,6 +799,14 @@ struct ParseOutput {
     continuity_commands: Vec<ContinuityCommand>,
 }
 
+/// Characters framing a TUI input box
+const BORDER_CHARS: &[char] = &[' ', '│', '┃', '|', '║'];
+
+/// Drop the left edge of an input box (`│ > `) so the prompt starts the line
+fn strip_border(line: &str) -> &str {
+    line.trim_start_matches(BORDER_CHARS)
+}
+
 /// Strip ANSI escape sequences from text
 pub fn strip_ansi(text: &str) -> String {
     ansi_pattern().replace_all(text, "").to_string()
@@ -855,6 +922,41 @@ mod tests {
         assert!(result.is_idle);
     }
 
+    #[test]
+    fn test_prompt_detection_on_screen() {
+        let screen = Arc::new(Screen::new(12, 40));
+        let mut parser = OutputParser::new("Alice".to_string(), r"^> $")
+            .with_prompts(&["> ", "$ "])
+            .with_screen(Arc::clone(&screen));
+        let mut process = |output: &[u8]| {
+            screen.feed(output);
+            parser.process(output)
+        };
+
+        // Nothing on screen: fall back to the last line of output
+        let result = process(b"> \x1b[2J");
+        assert_eq!(result.idle_method, Some(IdleMethod::Buffer));
+
+        // The prompt is the last thing written, but the cursor is back on the
+        // spinner row far above it
+        let busy = b"\x1b[2J\x1b[H* Working... (esc to interrupt)\x1b[10;1H> \x1b[1;31H";
+        let result = process(busy);
+        assert!(!result.is_idle);
+        assert_eq!(result.idle_method, None);
+
+        // Input box with the cursor parked on the hint row below it
+        let idle = b"\x1b[2J\x1b[H\xe2\x94\x82 >      \xe2\x94\x82\r\n? for shortcuts";
+        let result = process(idle);
+        assert!(result.is_idle);
+        assert_eq!(result.idle_method, Some(IdleMethod::Screen));
+
+        // Output near the cursor that merely ends in `>` is not a prompt
+        let code = b"\x1b[2J\x1b[Hfn main() -> Result<()>\r\n    let x = compute";
+        let result = process(code);
+        assert!(!result.is_idle);
+        assert_eq!(result.idle_method, None);
+    }
+
     #[test]
     fn test_strip_ansi() {
         let input = "\x1B[31mRed text\x1B[0m";
@@ -0,0 +1,232 @@
+//! Per-CLI injection profiles.
+//!
+//! Each agent CLI differs in how it wants input delivered: which keys submit
+//! and interrupt, whether it understands bracketed paste, how long it needs
+//! before Enter, what its prompt looks like, whether a newline in typed input
+//! submits early and how messages are worded. A profile bundles those
+//! settings so one CLI can be tuned without affecting the others. Profiles are
+//! picked with `--profile` or detected from the command being wrapped.
+
+use crate::template::DEFAULT_TEMPLATE;
+use std::path::Path;
+
+/// How message content is written to the PTY
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteStyle {
+    /// Bracketed paste when the child has enabled it, keystrokes otherwise
+    Auto,
+    /// Always wrap in bracketed paste markers
+    Bracketed,
+    /// Always write as plain keystrokes
+    Plain,
+}
+
+/// How newlines inside a message are sent when not pasting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewlineStyle {
+    /// Send newlines unchanged
+    Keep,
+    /// Replace newlines with spaces (for CLIs where any newline submits).
+    /// A last resort: messages go in as a bracketed paste whenever the CLI
+    /// has enabled it, keeping their line breaks.
+    Space,
+}
+
+impl NewlineStyle {
+    /// Apply the newline handling to message text
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            NewlineStyle::Keep => text.to_string(),
+            NewlineStyle::Space => text
+                .split(['\r', '\n'])
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// Injection settings for one CLI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectionProfile {
+    /// Profile name (as given to `--profile`)
+    pub name: &'static str,
+    /// Bytes sent to submit the message
+    pub submit: &'static [u8],
+    /// Bytes that interrupt the agent's current turn (for `interrupt` messages)
+    pub interrupt: &'static [u8],
+    /// How message content is written
+    pub paste: PasteStyle,
+    /// Delay between writing the message and submitting it
+    pub enter_delay_ms: u64,
+    /// Prompt suffixes that mean the CLI is waiting for input
+    pub prompts: &'static [&'static str],
+    /// Newline handling for keystroke (non-paste) injection
+    pub newlines: NewlineStyle,
+    /// Message template (overridden by `--message-template`)
+    pub template: &'static str,
+}
+
+/// Claude Code
+pub const CLAUDE: InjectionProfile = InjectionProfile {
+    name: "claude",
+    submit: b"\r",
+    interrupt: b"\x1b",
+    paste: PasteStyle::Auto,
+    enter_delay_ms: 50,
+    prompts: &["> "],
+    newlines: NewlineStyle::Keep,
+    template: DEFAULT_TEMPLATE,
+};
+
+/// OpenAI Codex CLI
+pub const CODEX: InjectionProfile = InjectionProfile {
+    name: "codex",
+    submit: b"\r",
+    interrupt: b"\x1b",
+    paste: PasteStyle::Auto,
+    enter_delay_ms: 100,
+    prompts: &["codex> ", "› "],
+    newlines: NewlineStyle::Keep,
+    template: DEFAULT_TEMPLATE,
+};
+
+/// Google Gemini CLI
+pub const GEMINI: InjectionProfile = InjectionProfile {
+    name: "gemini",
+    submit: b"\r",
+    interrupt: b"\x1b",
+    paste: PasteStyle::Auto,
+    enter_delay_ms: 100,
+    prompts: &[">>> ", "> "],
+    newlines: NewlineStyle::Space,
+    template: DEFAULT_TEMPLATE,
+};
+
+/// opencode
+pub const OPENCODE: InjectionProfile = InjectionProfile {
+    name: "opencode",
+    submit: b"\r",
+    interrupt: b"\x1b",
+    paste: PasteStyle::Auto,
+    enter_delay_ms: 100,
+    prompts: &["> "],
+    newlines: NewlineStyle::Space,
+    template: DEFAULT_TEMPLATE,
+};
+
+/// Anything else (shells, unknown CLIs): the historical defaults
+pub const GENERIC: InjectionProfile = InjectionProfile {
+    name: "generic",
+    submit: b"\r",
+    interrupt: b"\x03",
+    paste: PasteStyle::Auto,
+    enter_delay_ms: 50,
+    prompts: &["> ", "$ ", ">>> ", "codex> "],
+    newlines: NewlineStyle::Keep,
+    template: DEFAULT_TEMPLATE,
+};
+
+/// All known profiles
+pub const PROFILES: &[&InjectionProfile] = &[&CLAUDE, &CODEX, &GEMINI, &OPENCODE, &GENERIC];
+
+/// Launchers whose first argument names the real CLI (e.g. `npx gemini`)
+const LAUNCHERS: &[&str] = &["npx", "bunx", "node", "bun", "pnpm", "yarn", "env"];
+
+impl InjectionProfile {
+    /// Look up a profile by name
+    pub fn by_name(name: &str) -> Option<&'static InjectionProfile> {
+        PROFILES
+            .iter()
+            .copied()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Pick a profile from the wrapped command, falling back to `generic`
+    pub fn detect(command: &[String]) -> &'static InjectionProfile {
+        let mut args = command.iter().map(|arg| program_name(arg));
+
+        let program = match args.next() {
+            Some(program) if LAUNCHERS.contains(&program.as_str()) => {
+                // Skip launcher flags like `npx -y`
+                args.find(|arg| !arg.starts_with('-')).unwrap_or(program)
+            }
+            Some(program) => program,
+            None => return &GENERIC,
+        };
+
+        PROFILES
+            .iter()
+            .copied()
+            .find(|p| p.name != GENERIC.name && program.starts_with(p.name))
+            .unwrap_or(&GENERIC)
+    }
+
+    /// Comma-separated profile names (for error messages and help)
+    pub fn names() -> String {
+        PROFILES
+            .iter()
+            .map(|p| p.name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Lowercased file name of a command or package argument
+/// (`/usr/local/bin/claude` → `claude`, `@google/gemini-cli` → `gemini-cli`)
+fn program_name(arg: &str) -> String {
+    Path::new(arg)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_from_command() {
+        assert_eq!(
+            InjectionProfile::detect(&command(&["claude"])).name,
+            "claude"
+        );
+        assert_eq!(
+            InjectionProfile::detect(&command(&["/usr/local/bin/codex", "--full-auto"])).name,
+            "codex"
+        );
+        assert_eq!(
+            InjectionProfile::detect(&command(&["npx", "-y", "@google/gemini-cli"])).name,
+            "gemini"
+        );
+        assert_eq!(
+            InjectionProfile::detect(&command(&["sh", "-c", "cat"])).name,
+            "generic"
+        );
+        assert_eq!(InjectionProfile::detect(&[]).name, "generic");
+    }
+
+    #[test]
+    fn test_by_name() {
+        assert_eq!(InjectionProfile::by_name("Codex"), Some(&CODEX));
+        assert_eq!(InjectionProfile::by_name("vim"), None);
+        assert!(InjectionProfile::names().contains("opencode"));
+    }
+
+    #[test]
+    fn test_templates_parse() {
+        for profile in PROFILES {
+            assert!(crate::template::MessageTemplate::parse(profile.template).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_newline_style() {
+        assert_eq!(NewlineStyle::Keep.apply("a\nb"), "a\nb");
+        assert_eq!(NewlineStyle::Space.apply("a\n\nb\r\nc"), "a b c");
+    }
+}
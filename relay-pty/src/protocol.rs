@@ -3,12 +3,78 @@
 //! Defines the JSON message format for injection requests, responses,
 //! and parsed output commands.
 
+use crate::profile::{self, InjectionProfile};
+use crate::template::MessageTemplate;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Socket protocol version reported by `hello`.
+/// Bumped whenever an existing request or response changes incompatibly.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Request types this build understands (the `type` tag of [`InjectRequest`])
+pub const SUPPORTED_REQUESTS: &[&str] = &[
+    "hello",
+    "inject",
+    "send_enter",
+    "send_keys",
+    "cancel",
+    "reprioritize",
+    "list_queue",
+    "status",
+    "screen",
+    "subscribe",
+    "observe",
+    "subscribe_events",
+    "ack",
+    "subscribe_reviews",
+    "review",
+    "wait_idle",
+    "wait_for_pattern",
+    "shutdown",
+];
+
+/// Response types this build may send (the `type` tag of [`InjectResponse`])
+pub const SUPPORTED_RESPONSES: &[&str] = &[
+    "hello_ack",
+    "inject_result",
+    "captured_response",
+    "send_enter_result",
+    "send_keys_result",
+    "cancel_result",
+    "reprioritize_result",
+    "queue_list",
+    "status",
+    "screen",
+    "backpressure",
+    "subscribed",
+    "observing",
+    "output",
+    "events_subscribed",
+    "event",
+    "ack_result",
+    "reviews_subscribed",
+    "review_request",
+    "review_result",
+    "wait_result",
+    "shutdown_ack",
+    "unsupported",
+    "error",
+];
 
 /// Message sent to the injection socket
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum InjectRequest {
+    /// Handshake: ask for the protocol version and supported features
+    Hello {
+        /// Protocol version the client speaks (informational)
+        #[serde(default)]
+        protocol_version: Option<u32>,
+        /// Client name, for logging
+        #[serde(default)]
+        client: Option<String>,
+    },
     /// Inject a relay message into the agent
     Inject {
         /// Unique message ID for tracking
@@ -20,22 +86,283 @@ pub enum InjectRequest {
         /// Priority (lower = higher priority)
         #[serde(default)]
         priority: i32,
+        /// Optional metadata and delivery constraints
+        #[serde(flatten)]
+        options: InjectOptions,
     },
     /// Send just Enter key (for stuck input recovery)
     SendEnter {
         /// Message ID this is for (for tracking)
         id: String,
     },
+    /// Send named keys and literal text straight to the PTY
+    SendKeys {
+        /// Echoed in the result (for tracking)
+        #[serde(default)]
+        id: Option<String>,
+        /// Keys and text, sent in order
+        keys: Vec<KeyInput>,
+    },
+    /// Withdraw a message that is still waiting in the queue
+    Cancel {
+        /// ID of the queued message
+        id: String,
+    },
+    /// Change the priority of a message that is still waiting in the queue
+    Reprioritize {
+        /// ID of the queued message
+        id: String,
+        /// New priority (lower = higher priority)
+        priority: i32,
+    },
+    /// List every message still waiting in the queue
+    ListQueue,
     /// Query current status
     Status,
+    /// Snapshot of the child's screen as text
+    Screen,
+    /// Stream PTY output back over this connection as JSON lines
+    Subscribe {
+        /// Which representation of the output to send
+        #[serde(default)]
+        format: OutputFormat,
+        /// Replay up to this many bytes of recent output first (0 = start now)
+        #[serde(default)]
+        replay_bytes: usize,
+    },
+    /// Watch the agent: stream raw output, starting with a repaint of the
+    /// screen or a scrollback replay. The connection becomes read-only.
+    Observe {
+        /// How to bring the observer up to date
+        #[serde(default)]
+        start: ObserveStart,
+        /// For `scrollback`: replay at most this many bytes (default: all kept)
+        #[serde(default)]
+        replay_bytes: Option<usize>,
+    },
+    /// Stream parsed relay events (commands, continuity, stale outbox files)
+    /// back over this connection, starting with any unacknowledged ones
+    SubscribeEvents,
+    /// Acknowledge all events up to and including `seq`
+    Ack {
+        /// Highest sequence number the client has processed
+        seq: u64,
+    },
+    /// Act as a reviewer (review mode): receive each new message as a
+    /// `review_request` before it is injected
+    SubscribeReviews,
+    /// Decide on the message awaiting review (reviewers only)
+    Review {
+        /// ID of the message awaiting review
+        id: String,
+        /// What to do with it
+        decision: ReviewAction,
+        /// Replacement body (required for `edit`)
+        #[serde(default)]
+        body: Option<String>,
+        /// Why it was rejected (reported to the sender)
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Long poll: reply once the agent is idle
+    WaitIdle {
+        /// Echoed in the `wait_result`, to tell concurrent waits apart
+        #[serde(default)]
+        id: Option<String>,
+        /// Give up after this many ms
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+        /// Only count idle after the agent has been seen busy (e.g. right
+        /// after sending it something), instead of replying at once
+        #[serde(default)]
+        after_busy: bool,
+    },
+    /// Long poll: reply once output after the request matches a regex
+    WaitForPattern {
+        /// Echoed in the `wait_result`, to tell concurrent waits apart
+        #[serde(default)]
+        id: Option<String>,
+        /// Regex matched against ANSI-stripped output
+        pattern: String,
+        /// Give up after this many ms
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
     /// Graceful shutdown request
     Shutdown,
 }
 
+impl InjectRequest {
+    /// Whether the request leaves the agent and the queue untouched
+    /// (the only requests observers may send)
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            InjectRequest::Hello { .. }
+                | InjectRequest::ListQueue
+                | InjectRequest::Status
+                | InjectRequest::Screen
+                | InjectRequest::Subscribe { .. }
+                | InjectRequest::Observe { .. }
+                | InjectRequest::SubscribeEvents
+                | InjectRequest::WaitIdle { .. }
+                | InjectRequest::WaitForPattern { .. }
+        )
+    }
+}
+
+/// Optional metadata and delivery constraints for an inject request
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InjectOptions {
+    /// Conversation thread, available to message templates as `{thread}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    /// Drop the message if it hasn't been injected by this Unix time (ms)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Drop the message if it hasn't been injected this many ms after queueing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+    /// Hold the message until this Unix time (ms)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    /// Hold the message for this many ms after queueing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_after_ms: Option<u64>,
+    /// Send the agent's reply back as a `captured_response` after delivery
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub capture_response: bool,
+    /// Stop capturing the reply after this many ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_timeout_ms: Option<u64>,
+    /// Interrupt a busy agent instead of waiting for it to go idle
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupt: bool,
+}
+
+impl InjectOptions {
+    /// Earliest expiry implied by `expires_at` and `ttl_ms`, relative to now
+    pub fn deadline(&self) -> Option<Instant> {
+        let from_ttl = self.ttl_ms.map(instant_after_ms);
+        let from_timestamp = self.expires_at.map(instant_at_unix_ms);
+
+        match (from_ttl, from_timestamp) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Latest hold implied by `not_before` and `deliver_after_ms`, relative to now
+    pub fn not_before(&self) -> Option<Instant> {
+        let from_delay = self.deliver_after_ms.map(instant_after_ms);
+        let from_timestamp = self.not_before.map(instant_at_unix_ms);
+
+        match (from_delay, from_timestamp) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Sync metadata for a `capture_response` request
+    pub fn sync(&self) -> Option<SyncMeta> {
+        self.capture_response.then_some(SyncMeta {
+            blocking: true,
+            timeout_ms: self.capture_timeout_ms,
+        })
+    }
+}
+
+/// The instant `ms` milliseconds from now
+fn instant_after_ms(ms: u64) -> Instant {
+    Instant::now() + Duration::from_millis(ms)
+}
+
+/// The instant corresponding to a Unix timestamp in ms (now, if it's in the past)
+fn instant_at_unix_ms(unix_ms: u64) -> Instant {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    instant_after_ms(unix_ms.saturating_sub(now_ms))
+}
+
+/// Representation of PTY output sent to subscribers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Output exactly as the agent wrote it, including ANSI sequences
+    #[default]
+    Raw,
+    /// Output with ANSI escape sequences removed
+    Stripped,
+    /// Both raw and stripped output in each chunk
+    Both,
+}
+
+/// Where an observer's output stream starts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObserveStart {
+    /// Redraw the current screen from the screen model
+    #[default]
+    Repaint,
+    /// Replay recent raw output from the scrollback
+    Scrollback,
+}
+
+/// What decided that the agent is idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleMethod {
+    /// A prompt is shown at or near the cursor on the rendered screen
+    Screen,
+    /// The last line of raw output ends with a prompt (screen blank or unavailable)
+    Buffer,
+    /// The agent printed `->pty:ready`
+    ReadySignal,
+    /// No output for `idle_timeout_ms`
+    Silence,
+}
+
+/// Reviewer decision on a message in review mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewAction {
+    /// Inject the message as-is
+    Approve,
+    /// Inject with a replacement body
+    Edit,
+    /// Don't inject; the sender gets a `failed` status
+    Reject,
+}
+
+/// One item of a `send_keys` request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyInput {
+    /// A named key, e.g. `escape`, `ctrl-c`, `up`
+    Key { key: String },
+    /// Literal text, sent as-is
+    Text { text: String },
+}
+
 /// Response sent back through the injection socket
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum InjectResponse {
+    /// Handshake response
+    HelloAck {
+        /// Socket protocol version
+        protocol_version: u32,
+        /// relay-pty binary version
+        version: String,
+        /// Name of the agent behind this socket
+        agent: String,
+        /// Request types this build understands
+        requests: Vec<String>,
+        /// Response types this build may send
+        responses: Vec<String>,
+    },
     /// Injection result
     InjectResult {
         /// Message ID this response is for
@@ -48,6 +375,17 @@ pub enum InjectResponse {
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
     },
+    /// Agent output captured after a `capture_response` message was delivered
+    CapturedResponse {
+        /// Message ID this response is for
+        id: String,
+        /// Output from Enter until the agent went idle (ANSI-stripped, echoes removed)
+        text: String,
+        /// Capture stopped at the timeout or the next message rather than on idle
+        timed_out: bool,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
     /// SendEnter result (for stuck input recovery)
     SendEnterResult {
         /// Message ID this response is for
@@ -57,17 +395,80 @@ pub enum InjectResponse {
         /// Unix timestamp in milliseconds
         timestamp: u64,
     },
+    /// SendKeys result
+    SendKeysResult {
+        /// ID from the request
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        /// Whether the keys were written to the PTY
+        success: bool,
+        /// Number of bytes written
+        bytes: usize,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Cancel result (the message's own `cancelled` status is broadcast separately)
+    CancelResult {
+        /// Message ID that was cancelled
+        id: String,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Reprioritize result
+    ReprioritizeResult {
+        /// Message ID that was updated
+        id: String,
+        /// The message's new priority
+        priority: i32,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Pending messages, in the order they will be injected
+    QueueList {
+        /// One entry per queued message
+        messages: Vec<QueueEntry>,
+    },
     /// Status response
     Status {
         /// Whether agent appears idle (ready for injection)
         agent_idle: bool,
-        /// Number of messages in queue
+        /// How idleness was detected (absent while busy)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        idle_method: Option<IdleMethod>,
+        /// Whether injection is paused from the terminal (Ctrl-] p)
+        paused: bool,
+        /// Whether a human typed recently (injection is deferred until they stop)
+        user_active: bool,
+        /// Number of messages waiting in the queue (ready plus scheduled)
         queue_length: usize,
-        /// Cursor position [x, y]
+        /// Number of messages ready to inject
+        ready: usize,
+        /// Number of messages held until a scheduled time
+        scheduled: usize,
+        /// Queue capacity before backpressure
+        max_size: usize,
+        /// Message IDs currently remembered for deduplication
+        seen_count: usize,
+        /// Cursor position [x, y] on the child's screen (0-based)
         cursor_position: Option<[u16; 2]>,
         /// Milliseconds since last output
         last_output_ms: u64,
     },
+    /// What the child's terminal currently shows
+    Screen {
+        /// Screen height
+        rows: u16,
+        /// Screen width
+        cols: u16,
+        /// Cursor position [x, y] (0-based)
+        cursor: [u16; 2],
+        /// Whether the child has the cursor shown
+        cursor_visible: bool,
+        /// Whether the alternate screen is active (full-screen TUIs)
+        alternate_screen: bool,
+        /// Visible rows top to bottom, trailing spaces removed
+        lines: Vec<String>,
+    },
     /// Backpressure notification
     Backpressure {
         /// Current queue length
@@ -75,8 +476,103 @@ pub enum InjectResponse {
         /// Whether new messages are accepted
         accept: bool,
     },
+    /// Output subscription confirmed; `output` chunks follow
+    Subscribed {
+        /// Format of the chunks that will be streamed
+        format: OutputFormat,
+        /// Number of scrollback bytes replayed before live output
+        replayed_bytes: usize,
+    },
+    /// Observer mode confirmed; raw `output` chunks follow, the first one
+    /// bringing the observer up to date
+    Observing {
+        /// How the stream starts
+        start: ObserveStart,
+        /// Bytes in the first chunk (the repaint or the replayed scrollback)
+        initial_bytes: usize,
+    },
+    /// Chunk of PTY output (only sent to subscribed connections)
+    Output {
+        /// Raw output (lossless UTF-8; ANSI sequences included)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<String>,
+        /// Output with ANSI escape sequences stripped
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Event subscription confirmed; `event` lines follow
+    EventsSubscribed {
+        /// Number of unacknowledged events replayed before live events
+        replayed: usize,
+    },
+    /// Parsed relay event (only sent to event subscribers)
+    Event {
+        /// Sequence number to acknowledge once processed
+        seq: u64,
+        /// The event payload
+        event: Box<RelayEvent>,
+    },
+    /// Ack result
+    AckResult {
+        /// Sequence number that was acknowledged
+        seq: u64,
+        /// Number of events released from the replay buffer
+        released: usize,
+        /// Number of events still awaiting acknowledgement
+        pending: usize,
+    },
+    /// Reviewer role confirmed; `review_request` lines follow
+    ReviewsSubscribed,
+    /// A message awaiting review (only sent to reviewers)
+    ReviewRequest {
+        /// Message ID to use in the `review` request
+        id: String,
+        /// Sender name
+        from: String,
+        /// Full message body
+        body: String,
+        /// Priority (lower = higher priority)
+        priority: i32,
+        /// Conversation thread, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thread: Option<String>,
+    },
+    /// Review decision accepted
+    ReviewResult {
+        /// Message ID
+        id: String,
+        /// The decision applied
+        decision: ReviewAction,
+        /// Unix timestamp in milliseconds
+        timestamp: u64,
+    },
+    /// Outcome of a `wait_idle` or `wait_for_pattern` long poll
+    WaitResult {
+        /// ID from the wait request
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        /// Whether the condition was met (agent idle / pattern matched)
+        matched: bool,
+        /// Text that matched the pattern
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        /// Why the wait ended without a match
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        /// How long the wait took
+        elapsed_ms: u64,
+    },
     /// Shutdown acknowledged
     ShutdownAck,
+    /// The request type is not known to this build
+    Unsupported {
+        /// The `type` the client sent
+        request_type: String,
+        /// Human-readable explanation
+        message: String,
+    },
     /// Error response
     Error {
         /// Error message
@@ -92,14 +588,47 @@ pub enum InjectStatus {
     Queued,
     /// Currently being injected
     Injecting,
+    /// Interrupting the busy agent before injecting (`interrupt` messages)
+    Interrupting,
     /// Successfully delivered and echoed
     Delivered,
     /// Injection failed after retries
     Failed,
+    /// Withdrawn from the queue before injection
+    Cancelled,
+    /// Dropped because it outlived its `expires_at` / `ttl_ms`
+    Expired,
 }
 
-/// Synchronization metadata for blocking messages
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl InjectStatus {
+    /// Whether no further status updates will follow for the message
+    pub fn is_final(self) -> bool {
+        matches!(
+            self,
+            InjectStatus::Delivered
+                | InjectStatus::Failed
+                | InjectStatus::Cancelled
+                | InjectStatus::Expired
+        )
+    }
+
+    /// Wire name of the status
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InjectStatus::Queued => "queued",
+            InjectStatus::Injecting => "injecting",
+            InjectStatus::Interrupting => "interrupting",
+            InjectStatus::Delivered => "delivered",
+            InjectStatus::Failed => "failed",
+            InjectStatus::Cancelled => "cancelled",
+            InjectStatus::Expired => "expired",
+        }
+    }
+}
+
+/// Synchronization metadata for blocking messages (the sender waits for the
+/// agent's reply)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyncMeta {
     /// Whether sender should block awaiting response
     pub blocking: bool,
@@ -196,6 +725,19 @@ impl StaleOutboxFile {
     }
 }
 
+/// Event produced by parsing agent output or watching the outbox.
+/// Serialized as the wrapped type, which carries its own `type` field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RelayEvent {
+    /// Relay message, spawn or release command
+    Command(ParsedRelayCommand),
+    /// Continuity save/load/uncertain command
+    Continuity(ContinuityCommand),
+    /// Outbox file written without a trigger
+    StaleOutbox(StaleOutboxFile),
+}
+
 impl ParsedRelayCommand {
     pub fn new_message(from: String, to: String, body: String, raw: String) -> Self {
         Self {
@@ -259,6 +801,64 @@ impl ParsedRelayCommand {
     }
 }
 
+/// Message from a client on the attach socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AttachRequest {
+    /// Take control of the terminal (first message on a connection)
+    Attach {
+        /// Client terminal height
+        rows: u16,
+        /// Client terminal width
+        cols: u16,
+    },
+    /// Keystrokes for the agent
+    Input {
+        /// Bytes typed, as an array of byte values (raw input need not be UTF-8)
+        data: Vec<u8>,
+    },
+    /// The client terminal was resized
+    Resize { rows: u16, cols: u16 },
+    /// Leave the agent running and disconnect
+    Detach,
+}
+
+/// Message to a client on the attach socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AttachEvent {
+    /// Terminal output to write as-is (the first one repaints the screen)
+    Output { data: String },
+    /// Attach refused or failed; the connection is closed
+    Error { message: String },
+}
+
+/// Maximum number of body characters shown in a queue listing
+const QUEUE_PREVIEW_CHARS: usize = 80;
+
+/// Summary of a queued message (for `list_queue`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    /// Message ID
+    pub id: String,
+    /// Sender name
+    pub from: String,
+    /// Priority (lower = higher priority)
+    pub priority: i32,
+    /// Number of failed injection attempts so far
+    pub retries: u32,
+    /// Milliseconds since the message was (re)queued
+    pub age_ms: u64,
+    /// Milliseconds left before the message expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in_ms: Option<u64>,
+    /// Milliseconds until a scheduled message becomes due
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_in_ms: Option<u64>,
+    /// Start of the body, with newlines folded to spaces
+    pub preview: String,
+}
+
 /// Internal message for the injection queue
 #[derive(Debug, Clone)]
 pub struct QueuedMessage {
@@ -268,12 +868,38 @@ pub struct QueuedMessage {
     pub from: String,
     /// Message body
     pub body: String,
+    /// Conversation thread, if any
+    pub thread: Option<String>,
     /// Priority (lower = higher priority)
     pub priority: i32,
     /// Retry count
     pub retries: u32,
     /// Timestamp when queued
     pub queued_at: std::time::Instant,
+    /// Drop instead of injecting once this instant has passed
+    pub expires_at: Option<Instant>,
+    /// Don't inject before this instant
+    pub not_before: Option<Instant>,
+    /// Capture the agent's reply after delivery (if blocking)
+    pub sync: Option<SyncMeta>,
+    /// Interrupt a busy agent rather than wait for it
+    pub interrupt: bool,
+}
+
+/// Characters of a message ID in its short form (`[msg-123]` markers, status line)
+pub const SHORT_ID_CHARS: usize = 7;
+
+/// The first `n` characters of `s`, never splitting a multi-byte character
+pub fn prefix_chars(s: &str, n: usize) -> &str {
+    match s.char_indices().nth(n) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+/// Short form of a message ID
+pub fn short_id(id: &str) -> &str {
+    prefix_chars(id, SHORT_ID_CHARS)
 }
 
 impl QueuedMessage {
@@ -282,46 +908,71 @@ impl QueuedMessage {
             id,
             from,
             body,
+            thread: None,
             priority,
             retries: 0,
             queued_at: std::time::Instant::now(),
+            expires_at: None,
+            not_before: None,
+            sync: None,
+            interrupt: false,
         }
     }
 
-    /// Format as relay message for injection with escalating urgency based on retry count.
+    /// Whether the message may be injected at `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.not_before.is_none_or(|at| now >= at)
+    }
+
+    /// Whether the message outlived its expiry
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Summarize for a queue listing
+    pub fn queue_entry(&self) -> QueueEntry {
+        let mut preview: String = self
+            .body
+            .chars()
+            .take(QUEUE_PREVIEW_CHARS)
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        if self.body.chars().count() > QUEUE_PREVIEW_CHARS {
+            preview.push_str("...");
+        }
+
+        let now = Instant::now();
+        let ms_until = |at: Instant| at.saturating_duration_since(now).as_millis() as u64;
+
+        QueueEntry {
+            id: self.id.clone(),
+            from: self.from.clone(),
+            priority: self.priority,
+            retries: self.retries,
+            age_ms: now.duration_since(self.queued_at).as_millis() as u64,
+            expires_in_ms: self.expires_at.map(ms_until),
+            due_in_ms: self.not_before.filter(|at| *at > now).map(ms_until),
+            preview,
+        }
+    }
+
+    /// Format with the default template, escalating urgency by retry count.
     ///
     /// If the body is already formatted (starts with "Relay message from"), it will be used
     /// as-is to avoid double-formatting. This happens when the Node.js orchestrator has
     /// already called buildInjectionString() before sending to the socket.
     ///
-    /// Retry escalation (only applied to newly formatted messages):
+    /// Retry escalation:
     /// - Attempt 1 (retries=0): "Relay message from..."
     /// - Attempt 2 (retries=1): "[RETRY] Relay message from..."
     /// - Attempt 3+ (retries>=2): "[URGENT - PLEASE ACKNOWLEDGE] Relay message from..."
     pub fn format_for_injection(&self) -> String {
-        // Check if body is already formatted (from Node.js buildInjectionString)
-        // This prevents double-wrapping with "Relay message from..."
-        if self.body.starts_with("Relay message from ") {
-            // Already formatted - just apply retry prefixes if needed
-            return match self.retries {
-                0 => self.body.clone(),
-                1 => format!("[RETRY] {}", self.body),
-                _ => format!("[URGENT - PLEASE ACKNOWLEDGE] {}", self.body),
-            };
-        }
-
-        // Not pre-formatted - apply full formatting
-        let short_id = &self.id[..self.id.len().min(7)];
-        let base_msg = format!(
-            "Relay message from {} [{}]: {}",
-            self.from, short_id, self.body
-        );
+        self.format_with(&MessageTemplate::default())
+    }
 
-        match self.retries {
-            0 => base_msg,
-            1 => format!("[RETRY] {}", base_msg),
-            _ => format!("[URGENT - PLEASE ACKNOWLEDGE] {}", base_msg),
-        }
+    /// Format with a configured template (see `template.rs`)
+    pub fn format_with(&self, template: &MessageTemplate) -> String {
+        template.render(self)
     }
 }
 
@@ -346,6 +997,37 @@ pub struct Config {
     pub max_retries: u32,
     /// Delay between retries in milliseconds
     pub retry_delay_ms: u64,
+    /// How long to watch for an echo or agent activity after injecting
+    /// before retrying (0 = assume delivery)
+    pub verify_timeout_ms: u64,
+    /// CLI-specific injection settings
+    pub profile: &'static InjectionProfile,
+    /// How queued messages are formatted for injection
+    pub template: MessageTemplate,
+    /// Write message content to the PTY in chunks of at most this many bytes (0 = one write)
+    pub chunk_bytes: usize,
+    /// Pause between chunks in milliseconds
+    pub chunk_delay_ms: u64,
+    /// Bodies larger than this are saved to a file and replaced by a pointer (0 = never)
+    pub max_inline_bytes: usize,
+    /// Directory for bodies too large to inject inline
+    pub spill_dir: String,
+    /// Defer injection until the human has not typed for this long (0 = don't wait)
+    pub user_quiet_ms: u64,
+    /// Hold all injection while a human terminal is attached
+    pub pause_when_attached: bool,
+}
+
+/// Default directory for oversized bodies: under the working directory the
+/// agent runs in, so it can read them even when sandboxed there
+pub fn default_spill_dir(name: &str) -> String {
+    std::env::current_dir()
+        .unwrap_or_default()
+        .join(".relay-pty")
+        .join("messages")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
 }
 
 impl Default for Config {
@@ -369,6 +1051,15 @@ impl Default for Config {
             command: vec![],
             max_retries: 3,
             retry_delay_ms: 300,
+            verify_timeout_ms: 3000,
+            profile: &profile::GENERIC,
+            template: MessageTemplate::default(),
+            chunk_bytes: 1024,
+            chunk_delay_ms: 10,
+            max_inline_bytes: 32 * 1024,
+            spill_dir: default_spill_dir("agent"),
+            user_quiet_ms: 2000,
+            pause_when_attached: false,
         }
     }
 }
@@ -385,10 +1076,85 @@ mod tests {
             from: "Alice".to_string(),
             body: "Hello!".to_string(),
             priority: 0,
+            options: InjectOptions::default(),
         };
         let json = serde_json::to_string(&req).unwrap();
         assert!(json.contains("\"type\":\"inject\""));
         assert!(json.contains("\"from\":\"Alice\""));
+        assert!(!json.contains("ttl_ms"));
+    }
+
+    #[test]
+    fn test_inject_request_expiry_options() {
+        let json = r#"{"type":"inject","id":"m","from":"A","body":"B","ttl_ms":5000}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        match req {
+            InjectRequest::Inject { options, .. } => {
+                assert_eq!(options.ttl_ms, Some(5000));
+                assert_eq!(options.expires_at, None);
+                let deadline = options.deadline().unwrap();
+                assert!(deadline > Instant::now() + Duration::from_millis(4000));
+            }
+            _ => panic!("Expected inject request"),
+        }
+
+        // A timestamp in the past expires immediately; the earlier deadline wins
+        let options = InjectOptions {
+            expires_at: Some(1),
+            ttl_ms: Some(60_000),
+            ..Default::default()
+        };
+        assert!(options.deadline().unwrap() <= Instant::now());
+        assert_eq!(InjectOptions::default().deadline(), None);
+    }
+
+    #[test]
+    fn test_inject_request_schedule_options() {
+        let json = r#"{"type":"inject","id":"m","from":"A","body":"B","deliver_after_ms":300000}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        match req {
+            InjectRequest::Inject { options, .. } => {
+                let due = options.not_before().unwrap();
+                assert!(due > Instant::now() + Duration::from_secs(290));
+            }
+            _ => panic!("Expected inject request"),
+        }
+
+        // Both constraints must hold, so the later one wins
+        let options = InjectOptions {
+            not_before: Some(1),
+            deliver_after_ms: Some(60_000),
+            ..Default::default()
+        };
+        assert!(options.not_before().unwrap() > Instant::now() + Duration::from_secs(50));
+    }
+
+    #[test]
+    fn test_inject_request_capture_options() {
+        let json = r#"{"type":"inject","id":"m","from":"A","body":"B","capture_response":true,"capture_timeout_ms":9000}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        match req {
+            InjectRequest::Inject { options, .. } => {
+                assert_eq!(
+                    options.sync(),
+                    Some(SyncMeta {
+                        blocking: true,
+                        timeout_ms: Some(9000)
+                    })
+                );
+            }
+            _ => panic!("Expected inject request"),
+        }
+        assert_eq!(InjectOptions::default().sync(), None);
+
+        let response = InjectResponse::CapturedResponse {
+            id: "m".to_string(),
+            text: "42".to_string(),
+            timed_out: false,
+            timestamp: 1,
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains("\"type\":\"captured_response\""));
     }
 
     #[test]
@@ -403,6 +1169,137 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_subscribe_request_defaults() {
+        let json = r#"{"type":"subscribe"}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        match req {
+            InjectRequest::Subscribe {
+                format,
+                replay_bytes,
+            } => {
+                assert_eq!(format, OutputFormat::Raw);
+                assert_eq!(replay_bytes, 0);
+            }
+            _ => panic!("Expected subscribe request"),
+        }
+
+        let json = r#"{"type":"subscribe","format":"stripped","replay_bytes":4096}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            req,
+            InjectRequest::Subscribe {
+                format: OutputFormat::Stripped,
+                replay_bytes: 4096
+            }
+        ));
+    }
+
+    #[test]
+    fn test_short_id_never_splits_characters() {
+        assert_eq!(short_id("msg-1234567890"), "msg-123");
+        assert_eq!(short_id("msgé日本語テキスト"), "msgé日本語");
+        assert_eq!(short_id("m1"), "m1");
+        assert_eq!(prefix_chars("日本", 0), "");
+    }
+
+    #[test]
+    fn test_observe_request_defaults_and_read_only() {
+        let req: InjectRequest = serde_json::from_str(r#"{"type":"observe"}"#).unwrap();
+        assert!(matches!(
+            req,
+            InjectRequest::Observe {
+                start: ObserveStart::Repaint,
+                replay_bytes: None
+            }
+        ));
+        assert!(req.is_read_only());
+
+        let json = r#"{"type":"observe","start":"scrollback","replay_bytes":512}"#;
+        let req: InjectRequest = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            req,
+            InjectRequest::Observe {
+                start: ObserveStart::Scrollback,
+                replay_bytes: Some(512)
+            }
+        ));
+
+        assert!(InjectRequest::Status.is_read_only());
+        assert!(!InjectRequest::Shutdown.is_read_only());
+        assert!(!InjectRequest::SendEnter {
+            id: "x".to_string()
+        }
+        .is_read_only());
+    }
+
+    #[test]
+    fn test_supported_requests_match_request_enum() {
+        for request_type in SUPPORTED_REQUESTS {
+            let json = serde_json::json!({ "type": request_type }).to_string();
+            if let Err(e) = serde_json::from_str::<InjectRequest>(&json) {
+                assert!(
+                    !e.to_string().contains("unknown variant"),
+                    "{} is listed but not deserializable: {}",
+                    request_type,
+                    e
+                );
+            }
+        }
+
+        let err = serde_json::from_str::<InjectRequest>(r#"{"type":"teleport"}"#).unwrap_err();
+        assert!(err.to_string().contains("unknown variant"));
+    }
+
+    #[test]
+    fn test_attach_input_keeps_raw_bytes() {
+        // A lone Latin-1 byte (Alt-a on some terminals) isn't valid UTF-8
+        let input = AttachRequest::Input {
+            data: vec![0xe1, 0x1b, b'[', b'A'],
+        };
+        let json = serde_json::to_string(&input).unwrap();
+        assert_eq!(json, r#"{"type":"input","data":[225,27,91,65]}"#);
+        assert_eq!(serde_json::from_str::<AttachRequest>(&json).unwrap(), input);
+    }
+
+    #[test]
+    fn test_event_response_serializes_inner_type() {
+        let response = InjectResponse::Event {
+            seq: 7,
+            event: Box::new(RelayEvent::Continuity(ContinuityCommand::new(
+                "save".to_string(),
+                "notes".to_string(),
+            ))),
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["type"], "event");
+        assert_eq!(json["seq"], 7);
+        assert_eq!(json["event"]["type"], "continuity");
+        assert_eq!(json["event"]["action"], "save");
+    }
+
+    #[test]
+    fn test_queue_entry_preview() {
+        let msg = QueuedMessage::new(
+            "msg-1".to_string(),
+            "Alice".to_string(),
+            format!("line one\nline two {}", "x".repeat(100)),
+            3,
+        );
+        let entry = msg.queue_entry();
+        assert_eq!(entry.priority, 3);
+        assert!(entry.preview.starts_with("line one line two"));
+        assert!(entry.preview.ends_with("..."));
+        assert_eq!(entry.preview.chars().count(), QUEUE_PREVIEW_CHARS + 3);
+
+        let mut short = QueuedMessage::new("m".to_string(), "A".to_string(), "hi".to_string(), 0);
+        assert_eq!(short.queue_entry().preview, "hi");
+        assert_eq!(short.queue_entry().due_in_ms, None);
+
+        short.not_before = Some(Instant::now() + Duration::from_secs(60));
+        assert!(short.queue_entry().due_in_ms.unwrap() > 50_000);
+    }
+
     #[test]
     fn test_queued_message_format() {
         let msg = QueuedMessage::new(
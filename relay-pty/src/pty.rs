@@ -21,9 +21,6 @@ use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info};
 
-/// Original terminal settings stored outside Pty for thread-safety
-static mut ORIGINAL_TERMIOS: Option<libc::termios> = None;
-
 /// PTY handle for communicating with the child process
 pub struct Pty {
     /// Master file descriptor
@@ -146,56 +143,6 @@ impl Pty {
         Arc::clone(&self.running)
     }
 
-    /// Check if stdin is a TTY
-    pub fn is_tty() -> bool {
-        unsafe { libc::isatty(libc::STDIN_FILENO) != 0 }
-    }
-
-    /// Set raw mode on stdin for transparent terminal passthrough.
-    /// Returns Ok(false) if stdin is not a TTY (headless mode).
-    /// Returns Ok(true) if raw mode was successfully set.
-    pub fn set_raw_mode() -> Result<bool> {
-        let stdin_fd = libc::STDIN_FILENO;
-
-        // Check if stdin is a TTY - if not, skip raw mode (headless mode)
-        if !Self::is_tty() {
-            debug!("stdin is not a TTY, skipping raw mode (headless mode)");
-            return Ok(false);
-        }
-
-        unsafe {
-            // Save original settings
-            let mut termios: libc::termios = std::mem::zeroed();
-            if libc::tcgetattr(stdin_fd, &mut termios) != 0 {
-                anyhow::bail!("Failed to get terminal attributes");
-            }
-            ORIGINAL_TERMIOS = Some(termios);
-
-            // Set raw mode
-            let mut raw = termios;
-            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
-            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
-            raw.c_oflag &= !(libc::OPOST);
-
-            if libc::tcsetattr(stdin_fd, libc::TCSANOW, &raw) != 0 {
-                anyhow::bail!("Failed to set raw mode");
-            }
-        }
-
-        debug!("Terminal set to raw mode");
-        Ok(true)
-    }
-
-    /// Restore original terminal settings
-    pub fn restore_terminal() {
-        unsafe {
-            if let Some(ref termios) = ORIGINAL_TERMIOS {
-                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
-                debug!("Terminal restored");
-            }
-        }
-    }
-
     /// Resize the PTY
     pub fn resize(&self, rows: u16, cols: u16) -> Result<()> {
         let winsize = Winsize {
@@ -215,6 +162,24 @@ impl Pty {
         Ok(())
     }
 
+    /// Current PTY size as (rows, cols)
+    pub fn size(&self) -> Result<(u16, u16)> {
+        let mut winsize = Winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        unsafe {
+            if libc::ioctl(self.master_fd.as_raw_fd(), libc::TIOCGWINSZ, &mut winsize) < 0 {
+                anyhow::bail!("Failed to get PTY size");
+            }
+        }
+
+        Ok((winsize.ws_row, winsize.ws_col))
+    }
+
     /// Write data to the PTY (send to child)
     pub fn write_data(&self, data: &[u8]) -> Result<usize> {
         match write(&self.master_fd, data) {
@@ -267,7 +232,7 @@ impl Pty {
 
 impl Drop for Pty {
     fn drop(&mut self) {
-        Pty::restore_terminal();
+        crate::tty::restore_terminal();
 
         // Kill child if still running
         if self.is_running() {
@@ -5,11 +5,15 @@
 //! - Backpressure signaling when queue is full
 //! - Deduplication by message ID
 //! - Retry tracking
+//! - Cancellation and reprioritization of waiting messages
+//! - Expiry of messages that waited too long
+//! - Scheduled delivery (messages held until their not-before time)
 
-use crate::protocol::{InjectResponse, InjectStatus, QueuedMessage};
+use crate::protocol::{short_id, InjectResponse, InjectStatus, QueuedMessage};
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::time::{Duration, Instant};
+use thiserror::Error;
 use tokio::sync::{broadcast, Mutex, Notify};
 use tracing::{debug, info, warn};
 
@@ -20,6 +24,29 @@ const DEFAULT_SEEN_ID_TTL_SECS: u64 = 300;
 /// Default cleanup interval (60 seconds)
 const DEFAULT_CLEANUP_INTERVAL_SECS: u64 = 60;
 
+/// Number of finished message IDs remembered for cancel/reprioritize errors
+const FINISHED_HISTORY: usize = 256;
+
+/// Why a queued message couldn't be cancelled or reprioritized
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QueueError {
+    #[error("Message {0} is not in the queue")]
+    NotFound(String),
+    #[error("Message {0} is already being injected")]
+    Injecting(String),
+    #[error("Message {id} was already {}", status.as_str())]
+    Finished { id: String, status: InjectStatus },
+}
+
+/// Messages that have left the queue
+#[derive(Default)]
+struct Progress {
+    /// IDs dequeued for injection that haven't reached a final status
+    injecting: HashSet<String>,
+    /// Recently finished messages and their final status, oldest first
+    finished: VecDeque<(String, InjectStatus)>,
+}
+
 /// Wrapper for priority queue ordering (reversed for min-heap behavior)
 #[derive(Debug)]
 struct PriorityMessage(QueuedMessage);
@@ -61,6 +88,8 @@ pub struct MessageQueue {
     notify: Notify,
     /// Broadcast channel for sending responses (multiple receivers can subscribe)
     response_tx: broadcast::Sender<InjectResponse>,
+    /// Injecting and recently finished messages (std mutex: updated from report_result)
+    progress: std::sync::Mutex<Progress>,
     /// Last time we cleaned up expired seen_ids
     last_cleanup: Mutex<Instant>,
     /// TTL for seen message IDs (configurable for long-running sessions)
@@ -100,6 +129,7 @@ impl MessageQueue {
             max_size,
             notify: Notify::new(),
             response_tx,
+            progress: std::sync::Mutex::new(Progress::default()),
             last_cleanup: Mutex::new(Instant::now()),
             seen_id_ttl: Duration::from_secs(seen_ttl_secs),
             cleanup_interval: Duration::from_secs(cleanup_interval_secs),
@@ -182,7 +212,7 @@ impl MessageQueue {
     /// Get the next message from the queue
     pub async fn dequeue(&self) -> Option<QueuedMessage> {
         let mut queue = self.queue.lock().await;
-        queue.pop().map(|pm| pm.0)
+        self.pop_next(&mut queue).0
     }
 
     /// Wait for a message to be available and dequeue it
@@ -198,17 +228,73 @@ impl MessageQueue {
             // we start checking the queue will still wake us up.
             let notified = self.notify.notified();
 
-            // Check if there's a message
-            {
+            // Check if there's a message that is due
+            let next_due = {
                 let mut queue = self.queue.lock().await;
-                if let Some(pm) = queue.pop() {
-                    return pm.0;
+                match self.pop_next(&mut queue) {
+                    (Some(msg), _) => return msg,
+                    (None, next_due) => next_due,
+                }
+            };
+
+            // Wait for notification - safe because we created the future before checking.
+            // If only scheduled messages remain, also wake up when the first one is due.
+            match next_due {
+                Some(due) => {
+                    let _ = tokio::time::timeout_at(due.into(), notified).await;
                 }
+                None => notified.await,
             }
+        }
+    }
 
-            // Wait for notification - safe because we created the future before checking
-            notified.await;
+    /// Pop the highest-priority message that is due and hasn't expired.
+    /// Expired messages are dropped on the way and reported as `Expired`.
+    /// When nothing is due, also returns when the earliest scheduled message will be.
+    fn pop_next(
+        &self,
+        queue: &mut BinaryHeap<PriorityMessage>,
+    ) -> (Option<QueuedMessage>, Option<Instant>) {
+        let now = Instant::now();
+        let mut scheduled = Vec::new();
+        let mut found = None;
+
+        while let Some(PriorityMessage(msg)) = queue.pop() {
+            if msg.is_expired() {
+                info!(
+                    "Message {} expired after {}ms in queue, dropping",
+                    msg.id,
+                    msg.queued_at.elapsed().as_millis()
+                );
+                self.report_result(
+                    msg.id,
+                    InjectStatus::Expired,
+                    Some("Expired before injection".to_string()),
+                );
+                continue;
+            }
+            if !msg.is_due(now) {
+                scheduled.push(PriorityMessage(msg));
+                continue;
+            }
+            found = Some(self.start_injecting(msg));
+            break;
         }
+
+        let next_due = scheduled.iter().filter_map(|pm| pm.0.not_before).min();
+        queue.extend(scheduled);
+        (found, next_due)
+    }
+
+    /// Record that a message left the queue for injection.
+    /// Called with the queue lock held so cancel() always finds it in one place or the other.
+    fn start_injecting(&self, msg: QueuedMessage) -> QueuedMessage {
+        self.progress
+            .lock()
+            .unwrap()
+            .injecting
+            .insert(msg.id.clone());
+        msg
     }
 
     /// Peek at the next message without removing it
@@ -217,6 +303,22 @@ impl MessageQueue {
         queue.peek().map(|pm| pm.0.clone())
     }
 
+    /// Clone every pending message, in the order they will be dequeued:
+    /// ready messages by priority, then scheduled ones by due time
+    pub async fn pending(&self) -> Vec<QueuedMessage> {
+        let now = Instant::now();
+        let queue = self.queue.lock().await;
+        let mut messages: Vec<QueuedMessage> = queue.iter().map(|pm| pm.0.clone()).collect();
+        messages.sort_by(|a, b| {
+            let due_at = |msg: &QueuedMessage| msg.not_before.filter(|&at| at > now);
+            due_at(a)
+                .cmp(&due_at(b))
+                .then_with(|| a.priority.cmp(&b.priority))
+                .then_with(|| a.queued_at.cmp(&b.queued_at))
+        });
+        messages
+    }
+
     /// Get the current queue length
     pub async fn len(&self) -> usize {
         self.queue.lock().await.len()
@@ -233,15 +335,89 @@ impl MessageQueue {
         msg.queued_at = Instant::now();
 
         let mut queue = self.queue.lock().await;
+        self.progress.lock().unwrap().injecting.remove(&msg.id);
+        queue.push(PriorityMessage(msg));
+        self.notify.notify_one();
+    }
+
+    /// Put a dequeued message back untouched (same retries and place in line)
+    pub async fn requeue(&self, msg: QueuedMessage) {
+        let mut queue = self.queue.lock().await;
+        self.progress.lock().unwrap().injecting.remove(&msg.id);
         queue.push(PriorityMessage(msg));
         self.notify.notify_one();
     }
 
+    /// Withdraw a message that hasn't been dequeued yet.
+    /// Broadcasts `Cancelled` for it and returns the removed message.
+    pub async fn cancel(&self, id: &str) -> Result<QueuedMessage, QueueError> {
+        let msg = {
+            let mut queue = self.queue.lock().await;
+            match take_message(&mut queue, id) {
+                Some(msg) => msg,
+                None => return Err(self.not_queued_error(id)),
+            }
+        };
+
+        info!("Cancelled queued message {}", id);
+        self.forget(id).await;
+        self.report_result(
+            id.to_string(),
+            InjectStatus::Cancelled,
+            Some("Cancelled before injection".to_string()),
+        );
+        Ok(msg)
+    }
+
+    /// Change the priority of a message that hasn't been dequeued yet
+    pub async fn reprioritize(&self, id: &str, priority: i32) -> Result<(), QueueError> {
+        let mut queue = self.queue.lock().await;
+        let mut msg = take_message(&mut queue, id).ok_or_else(|| self.not_queued_error(id))?;
+
+        debug!(
+            "Reprioritized message {}: {} -> {}",
+            id, msg.priority, priority
+        );
+        msg.priority = priority;
+        queue.push(PriorityMessage(msg));
+        Ok(())
+    }
+
+    /// Explain why a message isn't in the queue
+    fn not_queued_error(&self, id: &str) -> QueueError {
+        let progress = self.progress.lock().unwrap();
+        if progress.injecting.contains(id) {
+            return QueueError::Injecting(id.to_string());
+        }
+        match progress.finished.iter().rev().find(|(done, _)| done == id) {
+            Some((_, status)) => QueueError::Finished {
+                id: id.to_string(),
+                status: *status,
+            },
+            None => QueueError::NotFound(id.to_string()),
+        }
+    }
+
+    /// The most recently finished message and its final status
+    pub fn last_finished(&self) -> Option<(String, InjectStatus)> {
+        let progress = self.progress.lock().unwrap();
+        progress.finished.back().cloned()
+    }
+
     /// Report injection result (broadcast to all subscribers)
     pub fn report_result(&self, id: String, status: InjectStatus, error: Option<String>) {
-        let short_id = &id[..id.len().min(8)];
+        let short_id = short_id(&id);
         debug!("Broadcasting status {:?} for message {}", status, short_id);
 
+        if status.is_final() {
+            let mut progress = self.progress.lock().unwrap();
+            progress.injecting.remove(&id);
+            progress.finished.push_back((id.clone(), status));
+            if progress.finished.len() > FINISHED_HISTORY {
+                progress.finished.pop_front();
+            }
+        }
+
         match self.response_tx.send(InjectResponse::InjectResult {
             id: id.clone(),
             status,
@@ -263,6 +439,21 @@ impl MessageQueue {
         }
     }
 
+    /// Report the agent's reply to a `capture_response` message
+    pub fn report_captured(&self, id: String, text: String, timed_out: bool) {
+        debug!(
+            "Broadcasting captured response for message {} ({} bytes)",
+            short_id(&id),
+            text.len()
+        );
+        let _ = self.response_tx.send(InjectResponse::CapturedResponse {
+            id,
+            text,
+            timed_out,
+            timestamp: current_timestamp_ms(),
+        });
+    }
+
     /// Clear seen IDs (for long-running sessions)
     pub async fn clear_seen(&self) {
         let mut seen = self.seen_ids.lock().await;
@@ -297,30 +488,53 @@ impl MessageQueue {
     pub async fn mark_delivered(&self, id: &str) {
         let mut seen = self.seen_ids.lock().await;
         if seen.remove(id).is_some() {
-            debug!(
-                "Removed delivered message {} from seen set",
-                &id[..id.len().min(8)]
-            );
+            debug!("Removed delivered message {} from seen set", short_id(id));
         }
     }
 
+    /// Forget a withdrawn message's ID, so the sender can send it again
+    pub async fn forget(&self, id: &str) {
+        self.seen_ids.lock().await.remove(id);
+    }
+
     /// Get queue statistics
     pub async fn stats(&self) -> QueueStats {
         let queue = self.queue.lock().await;
         let seen = self.seen_ids.lock().await;
 
+        let now = Instant::now();
+        let scheduled = queue.iter().filter(|pm| !pm.0.is_due(now)).count();
+
         QueueStats {
             queue_length: queue.len(),
+            ready: queue.len() - scheduled,
+            scheduled,
             max_size: self.max_size,
             seen_count: seen.len(),
         }
     }
 }
 
+/// Remove a message from the heap by ID
+fn take_message(queue: &mut BinaryHeap<PriorityMessage>, id: &str) -> Option<QueuedMessage> {
+    let mut messages = std::mem::take(queue).into_vec();
+    let msg = messages
+        .iter()
+        .position(|pm| pm.0.id == id)
+        .map(|index| messages.swap_remove(index).0);
+    *queue = BinaryHeap::from(messages);
+    msg
+}
+
 /// Queue statistics
 #[derive(Debug, Clone)]
 pub struct QueueStats {
+    /// Messages waiting in the queue, scheduled or not
     pub queue_length: usize,
+    /// Messages ready to inject now
+    pub ready: usize,
+    /// Messages waiting for their not-before time
+    pub scheduled: usize,
     pub max_size: usize,
     pub seen_count: usize,
 }
@@ -443,4 +657,236 @@ mod tests {
             .await;
         assert!(!result);
     }
+
+    #[tokio::test]
+    async fn test_cancel_queued_message() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        for id in ["keep", "drop"] {
+            queue
+                .enqueue(QueuedMessage::new(
+                    id.to_string(),
+                    "A".to_string(),
+                    "Body".to_string(),
+                    0,
+                ))
+                .await;
+        }
+
+        let cancelled = queue.cancel("drop").await.unwrap();
+        assert_eq!(cancelled.id, "drop");
+        assert_eq!(queue.len().await, 1);
+
+        // Skip the two Queued broadcasts
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        match rx.recv().await.unwrap() {
+            InjectResponse::InjectResult { id, status, .. } => {
+                assert_eq!(id, "drop");
+                assert_eq!(status, InjectStatus::Cancelled);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        assert_eq!(
+            queue.cancel("drop").await.unwrap_err(),
+            QueueError::Finished {
+                id: "drop".to_string(),
+                status: InjectStatus::Cancelled
+            }
+        );
+        assert_eq!(
+            queue.cancel("missing").await.unwrap_err(),
+            QueueError::NotFound("missing".to_string())
+        );
+        // The ID can be sent again
+        assert!(
+            queue
+                .enqueue(QueuedMessage::new(
+                    "drop".to_string(),
+                    "A".to_string(),
+                    "Body".to_string(),
+                    0,
+                ))
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_rejects_injecting_and_delivered() {
+        let (tx, _rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        queue
+            .enqueue(QueuedMessage::new(
+                "msg".to_string(),
+                "A".to_string(),
+                "Body".to_string(),
+                0,
+            ))
+            .await;
+        let msg = queue.dequeue().await.unwrap();
+
+        assert_eq!(
+            queue.cancel(&msg.id).await.unwrap_err(),
+            QueueError::Injecting("msg".to_string())
+        );
+        assert_eq!(
+            queue.reprioritize(&msg.id, 1).await.unwrap_err(),
+            QueueError::Injecting("msg".to_string())
+        );
+
+        queue.report_result(msg.id.clone(), InjectStatus::Delivered, None);
+        let err = queue.cancel(&msg.id).await.unwrap_err();
+        assert_eq!(err.to_string(), "Message msg was already delivered");
+    }
+
+    #[tokio::test]
+    async fn test_expired_messages_dropped_at_dequeue() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        let mut stale = QueuedMessage::new(
+            "stale".to_string(),
+            "A".to_string(),
+            "Old news".to_string(),
+            0,
+        );
+        stale.expires_at = Some(Instant::now());
+        queue.enqueue(stale).await;
+        queue
+            .enqueue(QueuedMessage::new(
+                "fresh".to_string(),
+                "A".to_string(),
+                "Still relevant".to_string(),
+                5,
+            ))
+            .await;
+
+        assert_eq!(queue.dequeue().await.unwrap().id, "fresh");
+
+        // Skip the two Queued broadcasts
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+        match rx.recv().await.unwrap() {
+            InjectResponse::InjectResult { id, status, .. } => {
+                assert_eq!(id, "stale");
+                assert_eq!(status, InjectStatus::Expired);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        assert_eq!(
+            queue.cancel("stale").await.unwrap_err().to_string(),
+            "Message stale was already expired"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_message_held_until_due() {
+        let (tx, _rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        let mut later = QueuedMessage::new(
+            "later".to_string(),
+            "A".to_string(),
+            "Check CI".to_string(),
+            0,
+        );
+        later.not_before = Some(Instant::now() + Duration::from_millis(100));
+        queue.enqueue(later).await;
+
+        // Not due yet, even though it has the best priority
+        assert!(queue.dequeue().await.is_none());
+        let stats = queue.stats().await;
+        assert_eq!(stats.queue_length, 1);
+        assert_eq!(stats.ready, 0);
+        assert_eq!(stats.scheduled, 1);
+
+        queue
+            .enqueue(QueuedMessage::new(
+                "now".to_string(),
+                "A".to_string(),
+                "Ready".to_string(),
+                10,
+            ))
+            .await;
+        assert_eq!(queue.wait_and_dequeue().await.id, "now");
+
+        let start = Instant::now();
+        let msg = tokio::time::timeout(Duration::from_secs(2), queue.wait_and_dequeue())
+            .await
+            .expect("scheduled message should be delivered once due");
+        assert_eq!(msg.id, "later");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_pending_lists_in_dequeue_order() {
+        let (tx, _rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        for (id, priority) in [("b", 5), ("a", 1), ("c", 5)] {
+            queue
+                .enqueue(QueuedMessage::new(
+                    id.to_string(),
+                    "A".to_string(),
+                    "Body".to_string(),
+                    priority,
+                ))
+                .await;
+        }
+
+        let ids: Vec<String> = queue.pending().await.into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(queue.len().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_pending_lists_scheduled_after_ready() {
+        let (tx, _rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        for (id, priority, delay_ms) in [
+            ("late", 0, Some(60_000)),
+            ("ready", 5, None),
+            ("soon", 1, Some(30_000)),
+            ("urgent", 0, None),
+        ] {
+            let mut msg = QueuedMessage::new(
+                id.to_string(),
+                "A".to_string(),
+                "Body".to_string(),
+                priority,
+            );
+            msg.not_before = delay_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+            queue.enqueue(msg).await;
+        }
+
+        let ids: Vec<String> = queue.pending().await.into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec!["urgent", "ready", "soon", "late"]);
+    }
+
+    #[tokio::test]
+    async fn test_reprioritize_changes_dequeue_order() {
+        let (tx, _rx) = broadcast::channel(16);
+        let queue = MessageQueue::new(10, tx);
+
+        for (id, priority) in [("first", 1), ("second", 5)] {
+            queue
+                .enqueue(QueuedMessage::new(
+                    id.to_string(),
+                    "A".to_string(),
+                    "Body".to_string(),
+                    priority,
+                ))
+                .await;
+        }
+
+        queue.reprioritize("second", 0).await.unwrap();
+
+        assert_eq!(queue.dequeue().await.unwrap().id, "second");
+        assert_eq!(queue.dequeue().await.unwrap().id, "first");
+    }
 }
@@ -0,0 +1,319 @@
+//! Answers to terminal queries in headless mode.
+//!
+//! CLIs probe the terminal and some wait for the reply before drawing
+//! anything. With no real terminal attached nobody would answer, so this
+//! module does, consistently describing a plain VT220-class terminal:
+//! - `CSI 6n` / `CSI ?6n` cursor position, from the screen model
+//! - `CSI 5n` device status
+//! - `CSI c` / `CSI >c` primary and secondary device attributes
+//! - `CSI >q` XTVERSION
+//! - `OSC 10;?` / `OSC 11;?` foreground and background colour
+//! - `CSI ?u` kitty keyboard flags (always 0: keys are sent legacy-style)
+//! - `CSI ?Ps$p` / `CSI Ps$p` DECRQM mode reports
+//!
+//! Output is fed to the screen up to each query, so a cursor report
+//! reflects what was drawn before the query, not the whole chunk.
+
+use crate::modes::TerminalModes;
+use crate::screen::Screen;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Longest CSI/OSC body we buffer before giving up on a sequence
+const MAX_QUERY_BYTES: usize = 64;
+
+/// Reported foreground colour (light on dark)
+const FOREGROUND: &str = "rgb:ffff/ffff/ffff";
+
+/// Reported background colour
+const BACKGROUND: &str = "rgb:0000/0000/0000";
+
+/// A query found in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Query {
+    /// CPR; `private` for the DEC form (`CSI ?6n`)
+    CursorPosition {
+        private: bool,
+    },
+    DeviceStatus,
+    PrimaryAttributes,
+    SecondaryAttributes,
+    Version,
+    /// OSC 10 (foreground) or 11 (background); `bel` if terminated by BEL
+    Color {
+        code: u8,
+        bel: bool,
+    },
+    KeyboardFlags,
+    /// DECRQM; `private` for DEC modes
+    Mode {
+        mode: u16,
+        private: bool,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    /// Saw ESC
+    Escape,
+    /// Inside ESC [ ... collecting parameters and intermediates
+    Csi,
+    /// Inside ESC ] ... collecting the string
+    Osc,
+    /// Saw ESC inside an OSC string
+    OscEscape,
+}
+
+/// Finds queries in output, keeping state between chunks
+#[derive(Debug, Default)]
+struct Scanner {
+    state: ScanState,
+    body: Vec<u8>,
+}
+
+impl Scanner {
+    /// Advance by one byte, returning a query it completes
+    fn advance(&mut self, byte: u8) -> Option<Query> {
+        let (state, query) = match (self.state, byte) {
+            (ScanState::Osc, 0x07) => (ScanState::Ground, parse_osc(&self.body, true)),
+            (ScanState::Osc, 0x1b) => (ScanState::OscEscape, None),
+            (ScanState::OscEscape, b'\\') => (ScanState::Ground, parse_osc(&self.body, false)),
+            (_, 0x1b) => (ScanState::Escape, None),
+            (ScanState::Escape | ScanState::OscEscape, b'[' | b']') => {
+                self.body.clear();
+                let state = if byte == b'[' {
+                    ScanState::Csi
+                } else {
+                    ScanState::Osc
+                };
+                (state, None)
+            }
+            (ScanState::Csi, 0x40..=0x7e) => (ScanState::Ground, parse_csi(&self.body, byte)),
+            (ScanState::Csi, 0x20..=0x3f) | (ScanState::Osc, _)
+                if self.body.len() < MAX_QUERY_BYTES =>
+            {
+                self.body.push(byte);
+                (self.state, None)
+            }
+            _ => (ScanState::Ground, None),
+        };
+        self.state = state;
+        query
+    }
+}
+
+/// Interpret a complete CSI sequence (`body` is everything between `[` and `final_byte`)
+fn parse_csi(body: &[u8], final_byte: u8) -> Option<Query> {
+    let (private, rest) = match body.first() {
+        Some(&marker @ (b'?' | b'>' | b'<' | b'=')) => (Some(marker), &body[1..]),
+        _ => (None, body),
+    };
+    let is_default = rest.is_empty() || rest == b"0";
+
+    match (private, rest, final_byte) {
+        (None | Some(b'?'), b"6", b'n') => Some(Query::CursorPosition {
+            private: private.is_some(),
+        }),
+        (None, b"5", b'n') => Some(Query::DeviceStatus),
+        (None, _, b'c') if is_default => Some(Query::PrimaryAttributes),
+        (Some(b'>'), _, b'c') if is_default => Some(Query::SecondaryAttributes),
+        (Some(b'>'), _, b'q') if is_default => Some(Query::Version),
+        (Some(b'?'), b"", b'u') => Some(Query::KeyboardFlags),
+        (None | Some(b'?'), [digits @ .., b'$'], b'p') => {
+            let mode = std::str::from_utf8(digits).ok()?.parse().ok()?;
+            Some(Query::Mode {
+                mode,
+                private: private.is_some(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Interpret a complete OSC string
+fn parse_osc(body: &[u8], bel: bool) -> Option<Query> {
+    match body {
+        b"10;?" => Some(Query::Color { code: 10, bel }),
+        b"11;?" => Some(Query::Color { code: 11, bel }),
+        _ => None,
+    }
+}
+
+/// Keeps the screen model fed and answers queries on behalf of a terminal
+pub struct QueryResponder {
+    screen: Arc<Screen>,
+    modes: Arc<TerminalModes>,
+    scanner: Scanner,
+    /// Whether to answer (false while a real terminal answers for itself)
+    active: bool,
+}
+
+impl QueryResponder {
+    /// Create a responder; `active` when no real terminal is attached
+    pub fn new(screen: Arc<Screen>, modes: Arc<TerminalModes>, active: bool) -> Self {
+        Self {
+            screen,
+            modes,
+            scanner: Scanner::default(),
+            active,
+        }
+    }
+
+    /// Start or stop answering (stop while a real terminal is attached)
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Feed a chunk of child output to the screen and return the replies to
+    /// any queries in it (to be written back to the child)
+    pub fn observe(&mut self, data: &[u8]) -> Vec<u8> {
+        if !self.active {
+            self.screen.feed(data);
+            return Vec::new();
+        }
+
+        let mut reply = Vec::new();
+        let mut fed = 0;
+        for (i, &byte) in data.iter().enumerate() {
+            if let Some(query) = self.scanner.advance(byte) {
+                self.screen.feed(&data[fed..=i]);
+                fed = i + 1;
+                debug!("Answering terminal query {:?}", query);
+                reply.extend(self.answer(query));
+            }
+        }
+        self.screen.feed(&data[fed..]);
+        reply
+    }
+
+    fn answer(&self, query: Query) -> Vec<u8> {
+        match query {
+            Query::CursorPosition { private } => {
+                let [x, y] = self.screen.cursor_position();
+                let (row, col) = (y as u32 + 1, x as u32 + 1);
+                if private {
+                    format!("\x1b[?{};{};1R", row, col).into_bytes()
+                } else {
+                    format!("\x1b[{};{}R", row, col).into_bytes()
+                }
+            }
+            Query::DeviceStatus => b"\x1b[0n".to_vec(),
+            // VT220 with ANSI colour
+            Query::PrimaryAttributes => b"\x1b[?62;22c".to_vec(),
+            Query::SecondaryAttributes => b"\x1b[>1;10;0c".to_vec(),
+            Query::Version => {
+                format!("\x1bP>|relay-pty({})\x1b\\", env!("CARGO_PKG_VERSION")).into_bytes()
+            }
+            Query::Color { code, bel } => {
+                let color = if code == 10 { FOREGROUND } else { BACKGROUND };
+                let end = if bel { "\x07" } else { "\x1b\\" };
+                format!("\x1b]{};{}{}", code, color, end).into_bytes()
+            }
+            Query::KeyboardFlags => b"\x1b[?0u".to_vec(),
+            Query::Mode { mode, private } => {
+                // 1 = set, 2 = reset, 0 = not recognized
+                let state = if private {
+                    self.private_mode(mode)
+                } else {
+                    None
+                };
+                let value = match state {
+                    Some(true) => 1,
+                    Some(false) => 2,
+                    None => 0,
+                };
+                let marker = if private { "?" } else { "" };
+                format!("\x1b[{}{};{}$y", marker, mode, value).into_bytes()
+            }
+        }
+    }
+
+    fn private_mode(&self, mode: u16) -> Option<bool> {
+        match mode {
+            1 => Some(self.modes.application_cursor()),
+            2004 => Some(self.modes.bracketed_paste()),
+            _ => self.screen.private_mode(mode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder(active: bool) -> QueryResponder {
+        QueryResponder::new(
+            Arc::new(Screen::new(24, 80)),
+            Arc::new(TerminalModes::new()),
+            active,
+        )
+    }
+
+    #[test]
+    fn test_answers_device_queries() {
+        let mut r = responder(true);
+        assert_eq!(r.observe(b"\x1b[c"), b"\x1b[?62;22c");
+        assert_eq!(r.observe(b"\x1b[0c"), b"\x1b[?62;22c");
+        assert_eq!(r.observe(b"\x1b[>c"), b"\x1b[>1;10;0c");
+        assert_eq!(r.observe(b"\x1b[5n"), b"\x1b[0n");
+        assert_eq!(r.observe(b"\x1b[?u"), b"\x1b[?0u");
+        let version = String::from_utf8(r.observe(b"\x1b[>0q")).unwrap();
+        assert!(version.starts_with("\x1bP>|relay-pty("));
+        assert!(version.ends_with("\x1b\\"));
+
+        // Drawing sequences are not queries
+        assert!(r
+            .observe(b"\x1b[2J\x1b[1;1H\x1b[?25l\x1b[31mhi\x1b[>1u")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cursor_report_uses_screen_at_query() {
+        let mut r = responder(true);
+        // The cursor is reported where it was when the query was written
+        assert_eq!(r.observe(b"\x1b[3;5Habc\x1b[6n\r\nmore"), b"\x1b[3;8R");
+        assert_eq!(r.observe(b"\x1b[?6n"), b"\x1b[?4;5;1R");
+        assert_eq!(r.screen.cursor_position(), [4, 3]);
+    }
+
+    #[test]
+    fn test_colors_follow_terminator() {
+        let mut r = responder(true);
+        assert_eq!(
+            r.observe(b"\x1b]10;?\x07"),
+            b"\x1b]10;rgb:ffff/ffff/ffff\x07"
+        );
+        assert_eq!(
+            r.observe(b"\x1b]11;?\x1b\\"),
+            b"\x1b]11;rgb:0000/0000/0000\x1b\\"
+        );
+        // Setting the title is not a query
+        assert!(r.observe(b"\x1b]0;title\x07").is_empty());
+    }
+
+    #[test]
+    fn test_mode_reports() {
+        let mut r = responder(true);
+        r.modes.observe(b"\x1b[?2004h");
+        assert_eq!(r.observe(b"\x1b[?2004$p"), b"\x1b[?2004;1$y");
+        assert_eq!(r.observe(b"\x1b[?1$p"), b"\x1b[?1;2$y");
+        assert_eq!(r.observe(b"\x1b[?25l\x1b[?25$p"), b"\x1b[?25;2$y");
+        assert_eq!(r.observe(b"\x1b[?2026$p"), b"\x1b[?2026;0$y");
+        assert_eq!(r.observe(b"\x1b[4$p"), b"\x1b[4;0$y");
+    }
+
+    #[test]
+    fn test_split_queries_and_inactive() {
+        let mut r = responder(true);
+        assert!(r.observe(b"ok\x1b").is_empty());
+        assert!(r.observe(b"[?").is_empty());
+        assert_eq!(r.observe(b"6n"), b"\x1b[?1;3;1R");
+
+        // A real terminal answers for itself; the screen is still fed
+        let mut r = responder(false);
+        assert!(r.observe(b"hello\x1b[6n\x1b[c").is_empty());
+        assert_eq!(r.screen.cursor_position(), [5, 0]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Human review of incoming messages.
+//!
+//! In review mode the injector hands each new message to the review desk and
+//! waits for a decision before typing it into the agent. Reviewers are the
+//! human at the terminal (`Ctrl-]` commands) and socket clients that sent
+//! `subscribe_reviews`. Whoever answers first decides; a rejected message is
+//! reported back to the sender as failed. A message cancelled while awaiting
+//! review is withdrawn from the desk without a decision.
+
+use crate::protocol::QueuedMessage;
+use anyhow::{bail, Result};
+use std::sync::Mutex;
+use tokio::sync::{broadcast, oneshot};
+use tracing::info;
+
+/// Outcome of a review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewDecision {
+    /// Inject the message as-is
+    Approve,
+    /// Inject with this body instead
+    Edit(String),
+    /// Don't inject; the reason is reported to the sender
+    Reject(String),
+}
+
+struct PendingReview {
+    msg: QueuedMessage,
+    reply: oneshot::Sender<ReviewDecision>,
+}
+
+/// The message currently awaiting review and the reviewers watching for new ones
+pub struct ReviewDesk {
+    /// Held across lock-then-broadcast so subscribers never miss a request
+    pending: Mutex<Option<PendingReview>>,
+    requests_tx: broadcast::Sender<QueuedMessage>,
+}
+
+impl Default for ReviewDesk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReviewDesk {
+    pub fn new() -> Self {
+        let (requests_tx, _) = broadcast::channel(16);
+        Self {
+            pending: Mutex::new(None),
+            requests_tx,
+        }
+    }
+
+    /// Hold a message until a reviewer decides what to do with it.
+    /// Returns None if it was withdrawn first.
+    pub async fn review(&self, msg: QueuedMessage) -> Option<ReviewDecision> {
+        let (reply, decision) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            info!("Message {} from {} awaiting review", msg.id, msg.from);
+            let _ = self.requests_tx.send(msg.clone());
+            *pending = Some(PendingReview { msg, reply });
+        }
+        decision.await.ok()
+    }
+
+    /// The message awaiting review, if any
+    pub fn pending(&self) -> Option<QueuedMessage> {
+        let pending = self.pending.lock().unwrap();
+        pending.as_ref().map(|p| p.msg.clone())
+    }
+
+    /// Become a reviewer: the message awaiting review now (if any) and a
+    /// receiver for every later one
+    pub fn subscribe(&self) -> (Option<QueuedMessage>, broadcast::Receiver<QueuedMessage>) {
+        let pending = self.pending.lock().unwrap();
+        (
+            pending.as_ref().map(|p| p.msg.clone()),
+            self.requests_tx.subscribe(),
+        )
+    }
+
+    /// Withdraw a message awaiting review (it was cancelled).
+    /// Returns false if `id` isn't the one awaiting review.
+    pub fn withdraw(&self, id: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.as_ref().is_none_or(|p| p.msg.id != id) {
+            return false;
+        }
+        // Dropping the reply sender wakes review() with None
+        *pending = None;
+        info!("Message {} withdrawn from review", id);
+        true
+    }
+
+    /// Decide on the message awaiting review
+    pub fn decide(&self, id: &str, decision: ReviewDecision) -> Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.as_ref() {
+            Some(p) if p.msg.id == id => {}
+            Some(p) => bail!("Message {} is not awaiting review ({} is)", id, p.msg.id),
+            None => bail!("No message is awaiting review"),
+        }
+        if let ReviewDecision::Edit(ref body) = decision {
+            if body.trim().is_empty() {
+                bail!("Edited body is empty");
+            }
+        }
+
+        let review = pending.take().expect("checked above");
+        info!("Review of {}: {:?}", id, decision);
+        let _ = review.reply.send(decision);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn message(id: &str) -> QueuedMessage {
+        QueuedMessage::new(id.to_string(), "Alice".to_string(), "Hi".to_string(), 0)
+    }
+
+    #[tokio::test]
+    async fn test_review_waits_for_decision() {
+        let desk = Arc::new(ReviewDesk::new());
+        let (current, mut requests) = desk.subscribe();
+        assert!(current.is_none());
+
+        let task = {
+            let desk = Arc::clone(&desk);
+            tokio::spawn(async move { desk.review(message("msg-1")).await })
+        };
+
+        assert_eq!(requests.recv().await.unwrap().id, "msg-1");
+        assert_eq!(desk.pending().unwrap().id, "msg-1");
+        // A reviewer arriving late still sees it
+        assert_eq!(desk.subscribe().0.unwrap().id, "msg-1");
+
+        assert!(desk.decide("msg-2", ReviewDecision::Approve).is_err());
+        assert!(desk
+            .decide("msg-1", ReviewDecision::Edit("  ".to_string()))
+            .is_err());
+        desk.decide("msg-1", ReviewDecision::Edit("Hello".to_string()))
+            .unwrap();
+
+        assert_eq!(
+            task.await.unwrap(),
+            Some(ReviewDecision::Edit("Hello".to_string()))
+        );
+        assert!(desk.pending().is_none());
+        assert!(desk.decide("msg-1", ReviewDecision::Approve).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_ends_review_without_decision() {
+        let desk = Arc::new(ReviewDesk::new());
+        let (_, mut requests) = desk.subscribe();
+        let task = {
+            let desk = Arc::clone(&desk);
+            tokio::spawn(async move { desk.review(message("msg-1")).await })
+        };
+        requests.recv().await.unwrap();
+
+        assert!(!desk.withdraw("msg-2"));
+        assert!(desk.withdraw("msg-1"));
+        assert_eq!(task.await.unwrap(), None);
+        assert!(desk.pending().is_none());
+        assert!(desk.decide("msg-1", ReviewDecision::Approve).is_err());
+    }
+}
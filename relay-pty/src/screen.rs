@@ -0,0 +1,1087 @@
+//! Headless VT screen model.
+//!
+//! Feeds PTY output through a small VT100/xterm emulator so relay-pty knows
+//! what a human would see: the visible grid with attributes, the cursor, the
+//! scroll region and the alternate screen. Covers what agent TUIs actually
+//! use (cursor movement, erase, insert/delete, scrolling, SGR, DEC private
+//! modes); anything else is parsed and ignored. OSC/DCS strings are skipped.
+//! Wide (CJK, emoji) characters take two cells; combining marks are dropped.
+
+use crate::output::Utf8Decoder;
+use std::sync::Mutex;
+
+/// Tab stops are every 8 columns
+const TAB_WIDTH: usize = 8;
+
+/// Longest CSI parameter string kept (longer sequences are ignored)
+const MAX_CSI_PARAMS: usize = 64;
+
+/// Cell colour
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Color {
+    #[default]
+    Default,
+    /// 256-colour palette index (0-15 are the standard and bright colours)
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Graphic rendition of a cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attrs {
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+/// One character cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// The character (`'\0'` for the right half of a wide character)
+    pub ch: char,
+    pub attrs: Attrs,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            attrs: Attrs::default(),
+        }
+    }
+}
+
+/// What is on screen right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenSnapshot {
+    pub rows: u16,
+    pub cols: u16,
+    /// Cursor position [x, y] (0-based)
+    pub cursor: [u16; 2],
+    pub cursor_visible: bool,
+    /// Whether the alternate screen is active (full-screen TUIs)
+    pub alternate_screen: bool,
+    /// Visible rows, trailing spaces removed
+    pub lines: Vec<String>,
+}
+
+/// Text around the cursor (for prompt detection)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorRows {
+    /// The cursor row up to the cursor, trailing spaces kept
+    pub before_cursor: String,
+    /// Rows near the cursor row (including it), trailing spaces removed
+    pub lines: Vec<String>,
+}
+
+/// Screen model shared between the output loop and socket clients
+pub struct Screen {
+    term: Mutex<Terminal>,
+}
+
+impl Screen {
+    /// Create a blank screen of this size
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            term: Mutex::new(Terminal::new(rows, cols)),
+        }
+    }
+
+    /// Process a chunk of child output
+    pub fn feed(&self, data: &[u8]) {
+        self.term.lock().unwrap().feed(data);
+    }
+
+    /// Follow a resize of the child's terminal
+    pub fn resize(&self, rows: u16, cols: u16) {
+        self.term.lock().unwrap().resize(rows, cols);
+    }
+
+    /// Cursor position [x, y] (0-based)
+    pub fn cursor_position(&self) -> [u16; 2] {
+        let term = self.term.lock().unwrap();
+        [term.col as u16, term.row as u16]
+    }
+
+    /// Copy of the visible screen as text
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        self.term.lock().unwrap().snapshot()
+    }
+
+    /// Rows within `radius` of the cursor row, or None while nothing is drawn
+    pub fn cursor_rows(&self, radius: usize) -> Option<CursorRows> {
+        let term = self.term.lock().unwrap();
+        let drawn = term
+            .grid
+            .iter()
+            .any(|line| line.iter().any(|cell| cell.ch != ' '));
+        if !drawn {
+            return None;
+        }
+
+        let first = term.row.saturating_sub(radius);
+        let last = (term.row + radius).min(term.rows - 1);
+        Some(CursorRows {
+            before_cursor: line_text(&term.grid[term.row][..term.col]),
+            lines: term.grid[first..=last]
+                .iter()
+                .map(|line| line_text(line).trim_end().to_string())
+                .collect(),
+        })
+    }
+
+    /// Bytes that redraw the screen on a blank terminal of the same size:
+    /// the alternate screen if active, every cell with its attributes, the
+    /// scroll region, cursor position and visibility
+    pub fn repaint(&self) -> Vec<u8> {
+        self.term.lock().unwrap().repaint().into_bytes()
+    }
+
+    /// Scroll region as (top, bottom) rows, 0-based and inclusive
+    pub fn scroll_region(&self) -> (u16, u16) {
+        let term = self.term.lock().unwrap();
+        (term.scroll_top as u16, term.scroll_bottom as u16)
+    }
+
+    /// Bytes that put the cursor and attributes back the way the child left
+    /// them, after something else has drawn on the terminal
+    pub fn restore_cursor(&self) -> Vec<u8> {
+        self.term.lock().unwrap().cursor_state().into_bytes()
+    }
+
+    /// State of a DEC private mode the screen tracks (None for others)
+    pub fn private_mode(&self, mode: u16) -> Option<bool> {
+        let term = self.term.lock().unwrap();
+        match mode {
+            7 => Some(term.autowrap),
+            25 => Some(term.cursor_visible),
+            47 | 1047 | 1049 => Some(term.primary.is_some()),
+            _ => None,
+        }
+    }
+
+    /// The cell at row `y`, column `x`
+    pub fn cell(&self, y: u16, x: u16) -> Option<Cell> {
+        let term = self.term.lock().unwrap();
+        term.grid
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    attrs: Attrs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// Saw ESC
+    Escape,
+    /// ESC followed by a byte that takes one more (charset designation etc.)
+    EscapeArgument,
+    /// Inside ESC [ ...
+    Csi,
+    /// Inside an OSC / DCS / APC / PM / SOS string
+    String,
+    /// Saw ESC inside a string (expecting `\` to end it)
+    StringEscape,
+}
+
+type Grid = Vec<Vec<Cell>>;
+
+struct Terminal {
+    rows: usize,
+    cols: usize,
+    grid: Grid,
+    /// The primary screen, kept while the alternate screen is active
+    primary: Option<Grid>,
+    row: usize,
+    col: usize,
+    /// The last column was written; the next character wraps first
+    wrap_pending: bool,
+    attrs: Attrs,
+    saved: SavedCursor,
+    /// Scroll region (inclusive rows)
+    scroll_top: usize,
+    scroll_bottom: usize,
+    autowrap: bool,
+    cursor_visible: bool,
+    /// Last printed character (for REP)
+    last_char: Option<char>,
+    state: State,
+    params: String,
+    /// Private marker (`?`, `>`, `<`, `=`) of the current CSI
+    private: Option<char>,
+    /// Intermediate characters of the current CSI
+    intermediates: String,
+    decoder: Utf8Decoder,
+}
+
+impl Terminal {
+    fn new(rows: u16, cols: u16) -> Self {
+        let rows = (rows as usize).max(1);
+        let cols = (cols as usize).max(1);
+        Self {
+            rows,
+            cols,
+            grid: blank_grid(rows, cols),
+            primary: None,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            attrs: Attrs::default(),
+            saved: SavedCursor::default(),
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            cursor_visible: true,
+            last_char: None,
+            state: State::Ground,
+            params: String::new(),
+            private: None,
+            intermediates: String::new(),
+            decoder: Utf8Decoder::new(),
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        let text = self.decoder.decode(data);
+        for c in text.chars() {
+            self.advance(c);
+        }
+    }
+
+    fn advance(&mut self, c: char) {
+        match self.state {
+            State::Ground => match c {
+                '\x1b' => self.state = State::Escape,
+                c if (c as u32) < 0x20 || c == '\x7f' => self.control(c),
+                c => self.print(c),
+            },
+            State::Escape => {
+                self.state = State::Ground;
+                match c {
+                    '[' => {
+                        self.params.clear();
+                        self.intermediates.clear();
+                        self.private = None;
+                        self.state = State::Csi;
+                    }
+                    ']' | 'P' | 'X' | '^' | '_' => self.state = State::String,
+                    '(' | ')' | '*' | '+' | '-' | '.' | '/' | '#' | '%' => {
+                        self.state = State::EscapeArgument
+                    }
+                    '7' => self.save_cursor(),
+                    '8' => self.restore_cursor(),
+                    'D' => self.linefeed(),
+                    'E' => {
+                        self.col = 0;
+                        self.linefeed();
+                    }
+                    'M' => self.reverse_index(),
+                    'c' => self.reset(),
+                    '\x1b' => self.state = State::Escape,
+                    _ => {}
+                }
+            }
+            State::EscapeArgument => self.state = State::Ground,
+            State::Csi => match c {
+                '0'..='9' | ';' | ':' => {
+                    if self.params.len() < MAX_CSI_PARAMS {
+                        self.params.push(c);
+                    }
+                }
+                '?' | '>' | '<' | '=' if self.params.is_empty() => self.private = Some(c),
+                ' '..='/' => self.intermediates.push(c),
+                '@'..='~' => {
+                    self.state = State::Ground;
+                    if self.params.len() < MAX_CSI_PARAMS {
+                        self.csi(c);
+                    }
+                }
+                '\x1b' => self.state = State::Escape,
+                c if (c as u32) < 0x20 => self.control(c),
+                _ => self.state = State::Ground,
+            },
+            State::String => match c {
+                '\x07' => self.state = State::Ground,
+                '\x1b' => self.state = State::StringEscape,
+                _ => {}
+            },
+            State::StringEscape => {
+                self.state = if c == '\\' {
+                    State::Ground
+                } else {
+                    State::String
+                };
+            }
+        }
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\x08' => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            '\t' => {
+                self.col = ((self.col / TAB_WIDTH + 1) * TAB_WIDTH).min(self.cols - 1);
+                self.wrap_pending = false;
+            }
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+            '\r' => {
+                self.col = 0;
+                self.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let width = char_width(c);
+        if width == 0 {
+            return;
+        }
+        if self.wrap_pending {
+            self.col = 0;
+            self.linefeed();
+        }
+        if width == 2 && self.col + 1 >= self.cols {
+            if !self.autowrap || self.cols < 2 {
+                return;
+            }
+            self.grid[self.row][self.col] = self.blank();
+            self.col = 0;
+            self.linefeed();
+        }
+
+        self.grid[self.row][self.col] = Cell {
+            ch: c,
+            attrs: self.attrs,
+        };
+        if width == 2 {
+            self.grid[self.row][self.col + 1] = Cell {
+                ch: '\0',
+                attrs: self.attrs,
+            };
+        }
+        self.last_char = Some(c);
+
+        if self.col + width >= self.cols {
+            self.col = self.cols - 1;
+            self.wrap_pending = self.autowrap;
+        } else {
+            self.col += width;
+        }
+    }
+
+    /// A blank cell in the current background colour
+    fn blank(&self) -> Cell {
+        Cell {
+            ch: ' ',
+            attrs: Attrs {
+                bg: self.attrs.bg,
+                ..Attrs::default()
+            },
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    /// Scroll the scroll region up (content moves up, blank lines at the bottom)
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top + 1);
+        let blank = vec![self.blank(); self.cols];
+        self.grid[top..=bottom].rotate_left(n);
+        for line in &mut self.grid[bottom + 1 - n..=bottom] {
+            line.clone_from(&blank);
+        }
+    }
+
+    /// Scroll the scroll region down (content moves down, blank lines at the top)
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top + 1);
+        let blank = vec![self.blank(); self.cols];
+        self.grid[top..=bottom].rotate_right(n);
+        for line in &mut self.grid[top..top + n] {
+            line.clone_from(&blank);
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = SavedCursor {
+            row: self.row,
+            col: self.col,
+            attrs: self.attrs,
+        };
+    }
+
+    fn restore_cursor(&mut self) {
+        self.row = self.saved.row.min(self.rows - 1);
+        self.col = self.saved.col.min(self.cols - 1);
+        self.attrs = self.saved.attrs;
+        self.wrap_pending = false;
+    }
+
+    fn reset(&mut self) {
+        let (rows, cols) = (self.rows as u16, self.cols as u16);
+        let decoder = std::mem::take(&mut self.decoder);
+        *self = Terminal::new(rows, cols);
+        self.decoder = decoder;
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn csi(&mut self, action: char) {
+        // Sub-parameters (38:2:r:g:b) are treated like parameters
+        let params: Vec<usize> = self
+            .params
+            .split([';', ':'])
+            .map(|p| p.parse().unwrap_or(0))
+            .collect();
+        // Parameter `i`, with 0 / missing meaning `default`
+        let arg = |i: usize, default: usize| match params.get(i) {
+            Some(&v) if v > 0 => v,
+            _ => default,
+        };
+
+        if !self.intermediates.is_empty() {
+            return;
+        }
+        match self.private {
+            Some('?') => {
+                if action == 'h' || action == 'l' {
+                    for &mode in &params {
+                        self.set_private_mode(mode, action == 'h');
+                    }
+                }
+                return;
+            }
+            Some(_) => return,
+            None => {}
+        }
+
+        // Counts come straight from the child: never more than the screen holds
+        let n = arg(0, 1).min(self.rows.max(self.cols));
+        match action {
+            'A' => {
+                let top = if self.row >= self.scroll_top {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                self.move_to(self.row.saturating_sub(n).max(top), self.col);
+            }
+            'B' | 'e' => {
+                let bottom = if self.row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    self.rows - 1
+                };
+                self.move_to(self.row.saturating_add(n).min(bottom), self.col);
+            }
+            'C' | 'a' => self.move_to(self.row, self.col.saturating_add(n)),
+            'D' => self.move_to(self.row, self.col.saturating_sub(n)),
+            'E' => self.move_to(self.row.saturating_add(n), 0),
+            'F' => self.move_to(self.row.saturating_sub(n), 0),
+            'G' | '`' => self.move_to(self.row, n - 1),
+            'H' | 'f' => self.move_to(arg(0, 1) - 1, arg(1, 1) - 1),
+            'd' => self.move_to(n - 1, self.col),
+            'J' => self.erase_display(arg(0, 0)),
+            'K' => self.erase_line(arg(0, 0)),
+            'L' => self.insert_lines(n),
+            'M' => self.delete_lines(n),
+            '@' => self.insert_chars(n),
+            'P' => self.delete_chars(n),
+            'X' => {
+                let end = self.col.saturating_add(n).min(self.cols);
+                let blank = self.blank();
+                self.grid[self.row][self.col..end].fill(blank);
+                self.wrap_pending = false;
+            }
+            'S' => self.scroll_up(n),
+            'T' => self.scroll_down(n),
+            'b' => {
+                if let Some(c) = self.last_char {
+                    for _ in 0..n.min(self.rows * self.cols) {
+                        self.print(c);
+                    }
+                }
+            }
+            'r' => {
+                let top = arg(0, 1) - 1;
+                let bottom = arg(1, self.rows).min(self.rows) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            's' if params.len() <= 1 && arg(0, 0) == 0 => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            'm' => self.sgr(&params),
+            _ => {}
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: usize, enabled: bool) {
+        match mode {
+            7 => self.autowrap = enabled,
+            25 => self.cursor_visible = enabled,
+            47 | 1047 => self.set_alternate(enabled),
+            1048 => {
+                if enabled {
+                    self.save_cursor();
+                } else {
+                    self.restore_cursor();
+                }
+            }
+            1049 => {
+                if enabled {
+                    self.save_cursor();
+                    self.set_alternate(true);
+                } else {
+                    self.set_alternate(false);
+                    self.restore_cursor();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Switch to (a cleared) alternate screen, or back to the primary one
+    fn set_alternate(&mut self, enabled: bool) {
+        if enabled && self.primary.is_none() {
+            let alternate = blank_grid(self.rows, self.cols);
+            self.primary = Some(std::mem::replace(&mut self.grid, alternate));
+        } else if !enabled {
+            if let Some(primary) = self.primary.take() {
+                self.grid = primary;
+            }
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let blank = self.blank();
+        match mode {
+            0 => {
+                self.grid[self.row][self.col..].fill(blank);
+                for line in &mut self.grid[self.row + 1..] {
+                    line.fill(blank);
+                }
+            }
+            1 => {
+                for line in &mut self.grid[..self.row] {
+                    line.fill(blank);
+                }
+                self.grid[self.row][..=self.col].fill(blank);
+            }
+            2 | 3 => {
+                for line in &mut self.grid {
+                    line.fill(blank);
+                }
+            }
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let blank = self.blank();
+        let line = &mut self.grid[self.row];
+        match mode {
+            0 => line[self.col..].fill(blank),
+            1 => line[..=self.col].fill(blank),
+            2 => line.fill(blank),
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.row < self.scroll_top || self.row > self.scroll_bottom {
+            return;
+        }
+        let top = self.scroll_top;
+        self.scroll_top = self.row;
+        self.scroll_down(n);
+        self.scroll_top = top;
+        self.col = 0;
+        self.wrap_pending = false;
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.row < self.scroll_top || self.row > self.scroll_bottom {
+            return;
+        }
+        let top = self.scroll_top;
+        self.scroll_top = self.row;
+        self.scroll_up(n);
+        self.scroll_top = top;
+        self.col = 0;
+        self.wrap_pending = false;
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let blank = self.blank();
+        let col = self.col;
+        let line = &mut self.grid[self.row];
+        let n = n.min(line.len() - col);
+        line[col..].rotate_right(n);
+        line[col..col + n].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let blank = self.blank();
+        let col = self.col;
+        let line = &mut self.grid[self.row];
+        let n = n.min(line.len() - col);
+        line[col..].rotate_left(n);
+        let len = line.len();
+        line[len - n..].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn sgr(&mut self, params: &[usize]) {
+        if params.is_empty() {
+            self.attrs = Attrs::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => self.attrs = Attrs::default(),
+                1 => self.attrs.bold = true,
+                2 => self.attrs.dim = true,
+                3 => self.attrs.italic = true,
+                4 => self.attrs.underline = true,
+                7 => self.attrs.reverse = true,
+                22 => {
+                    self.attrs.bold = false;
+                    self.attrs.dim = false;
+                }
+                23 => self.attrs.italic = false,
+                24 => self.attrs.underline = false,
+                27 => self.attrs.reverse = false,
+                p @ 30..=37 => self.attrs.fg = Color::Indexed((p - 30) as u8),
+                p @ 40..=47 => self.attrs.bg = Color::Indexed((p - 40) as u8),
+                p @ 90..=97 => self.attrs.fg = Color::Indexed((p - 90 + 8) as u8),
+                p @ 100..=107 => self.attrs.bg = Color::Indexed((p - 100 + 8) as u8),
+                39 => self.attrs.fg = Color::Default,
+                49 => self.attrs.bg = Color::Default,
+                p @ (38 | 48) => {
+                    let (color, used) = extended_color(&params[i + 1..]);
+                    if let Some(color) = color {
+                        if p == 38 {
+                            self.attrs.fg = color;
+                        } else {
+                            self.attrs.bg = color;
+                        }
+                    }
+                    i += used;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn resize(&mut self, rows: u16, cols: u16) {
+        let rows = (rows as usize).max(1);
+        let cols = (cols as usize).max(1);
+
+        // Keep the cursor's line on screen when shrinking
+        if self.row >= rows {
+            let shift = self.row + 1 - rows;
+            self.grid.drain(..shift);
+            self.row -= shift;
+        }
+        resize_grid(&mut self.grid, rows, cols);
+        if let Some(ref mut primary) = self.primary {
+            resize_grid(primary, rows, cols);
+        }
+
+        self.rows = rows;
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.move_to(self.row, self.col);
+    }
+
+    fn repaint(&self) -> String {
+        let mut out = String::from("\x1b[0m");
+        if self.primary.is_some() {
+            out.push_str("\x1b[?1049h");
+        }
+        out.push_str("\x1b[H\x1b[2J");
+
+        for (y, line) in self.grid.iter().enumerate() {
+            // Blank default cells at the end are already drawn by the clear
+            let end = line
+                .iter()
+                .rposition(|cell| *cell != Cell::default())
+                .map_or(0, |x| x + 1);
+            if end == 0 {
+                continue;
+            }
+            out.push_str(&format!("\x1b[{};1H", y + 1));
+            let mut attrs = Attrs::default();
+            for cell in &line[..end] {
+                if cell.ch == '\0' {
+                    continue;
+                }
+                if cell.attrs != attrs {
+                    attrs = cell.attrs;
+                    out.push_str(&sgr(&attrs));
+                }
+                out.push(cell.ch);
+            }
+            out.push_str("\x1b[0m");
+        }
+
+        if self.scroll_top != 0 || self.scroll_bottom != self.rows - 1 {
+            out.push_str(&format!(
+                "\x1b[{};{}r",
+                self.scroll_top + 1,
+                self.scroll_bottom + 1
+            ));
+        }
+        if !self.autowrap {
+            out.push_str("\x1b[?7l");
+        }
+        out.push_str(&self.cursor_state());
+        out.push_str(if self.cursor_visible {
+            "\x1b[?25h"
+        } else {
+            "\x1b[?25l"
+        });
+        out
+    }
+
+    /// Cursor position and attributes. A pending wrap is recreated by
+    /// printing the last cell again, since moving the cursor clears it.
+    fn cursor_state(&self) -> String {
+        let mut out = String::new();
+        if self.wrap_pending {
+            let line = &self.grid[self.row];
+            // The right half of a wide character: print the whole character
+            let x = if line[self.col].ch == '\0' {
+                self.col.saturating_sub(1)
+            } else {
+                self.col
+            };
+            out.push_str(&format!("\x1b[{};{}H", self.row + 1, x + 1));
+            out.push_str(&sgr(&line[x].attrs));
+            out.push(line[x].ch);
+        } else {
+            out.push_str(&format!("\x1b[{};{}H", self.row + 1, self.col + 1));
+        }
+        out.push_str(&sgr(&self.attrs));
+        out
+    }
+
+    fn snapshot(&self) -> ScreenSnapshot {
+        let lines = self
+            .grid
+            .iter()
+            .map(|line| line_text(line).trim_end().to_string())
+            .collect();
+        ScreenSnapshot {
+            rows: self.rows as u16,
+            cols: self.cols as u16,
+            cursor: [self.col as u16, self.row as u16],
+            cursor_visible: self.cursor_visible,
+            alternate_screen: self.primary.is_some(),
+            lines,
+        }
+    }
+}
+
+/// The characters of a row (or part of one)
+fn line_text(cells: &[Cell]) -> String {
+    cells
+        .iter()
+        .map(|cell| cell.ch)
+        .filter(|&c| c != '\0')
+        .collect()
+}
+
+fn blank_grid(rows: usize, cols: usize) -> Grid {
+    vec![vec![Cell::default(); cols]; rows]
+}
+
+fn resize_grid(grid: &mut Grid, rows: usize, cols: usize) {
+    grid.resize(rows, vec![Cell::default(); cols]);
+    for line in grid.iter_mut() {
+        line.resize(cols, Cell::default());
+    }
+}
+
+/// SGR sequence selecting exactly these attributes
+fn sgr(attrs: &Attrs) -> String {
+    let mut params = vec!["0".to_string()];
+    for (on, code) in [
+        (attrs.bold, "1"),
+        (attrs.dim, "2"),
+        (attrs.italic, "3"),
+        (attrs.underline, "4"),
+        (attrs.reverse, "7"),
+    ] {
+        if on {
+            params.push(code.to_string());
+        }
+    }
+    for (color, base, bright, extended) in [(attrs.fg, 30, 90, 38), (attrs.bg, 40, 100, 48)] {
+        match color {
+            Color::Default => {}
+            Color::Indexed(n @ 0..=7) => params.push((base + n as u16).to_string()),
+            Color::Indexed(n @ 8..=15) => params.push((bright + n as u16 - 8).to_string()),
+            Color::Indexed(n) => params.push(format!("{};5;{}", extended, n)),
+            Color::Rgb(r, g, b) => params.push(format!("{};2;{};{};{}", extended, r, g, b)),
+        }
+    }
+    format!("\x1b[{}m", params.join(";"))
+}
+
+/// Parse the arguments of SGR 38/48: `5;n` or `2;r;g;b`.
+/// Returns the colour and how many parameters it used.
+fn extended_color(args: &[usize]) -> (Option<Color>, usize) {
+    match args {
+        [5, n, ..] => (Some(Color::Indexed(*n as u8)), 2),
+        [2, r, g, b, ..] => (Some(Color::Rgb(*r as u8, *g as u8, *b as u8)), 4),
+        [5] | [2, ..] => (None, args.len()),
+        _ => (None, 0),
+    }
+}
+
+/// Columns a character occupies: 0 for combining marks and zero-width
+/// characters, 2 for East Asian wide characters and emoji, 1 otherwise
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F | 0x20D0..=0x20FF => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(rows: u16, cols: u16, output: &str) -> Screen {
+        let screen = Screen::new(rows, cols);
+        screen.feed(output.as_bytes());
+        screen
+    }
+
+    #[test]
+    fn test_text_wrap_and_scroll() {
+        let s = screen(3, 5, "hello world\r\nline3\r\nline4");
+        let snap = s.snapshot();
+        assert_eq!(snap.lines, vec!["d", "line3", "line4"]);
+        // Last column written, wrap pending: the cursor stays on it
+        assert_eq!(snap.cursor, [4, 2]);
+        s.feed(b"!");
+        assert_eq!(s.snapshot().lines, vec!["line3", "line4", "!"]);
+        assert_eq!(s.cursor_position(), [1, 2]);
+    }
+
+    #[test]
+    fn test_cursor_movement_and_erase() {
+        let s = screen(4, 20, "abcdef\x1b[1;3HXY\x1b[2;1Hsecond\x1b[1;2H\x1b[K");
+        let snap = s.snapshot();
+        assert_eq!(snap.lines[0], "a");
+        assert_eq!(snap.lines[1], "second");
+        assert_eq!(snap.cursor, [1, 0]);
+        assert_eq!(s.cursor_position(), [1, 0]);
+
+        s.feed(b"\x1b[2J\x1b[H> ");
+        let snap = s.snapshot();
+        assert_eq!(snap.lines, vec![">", "", "", ""]);
+        assert_eq!(snap.cursor, [2, 0]);
+    }
+
+    #[test]
+    fn test_redraw_in_place_like_a_tui() {
+        // Claude-style: draw a box, then move up and rewrite the input line
+        let s = screen(
+            5,
+            30,
+            "Thinking...\r\n> draft\r\n\x1b[2A\r\x1b[2KDone.\x1b[1B\r\x1b[2K> ",
+        );
+        let snap = s.snapshot();
+        assert_eq!(snap.lines[0], "Done.");
+        assert_eq!(snap.lines[1], ">");
+        assert_eq!(snap.cursor, [2, 1]);
+    }
+
+    #[test]
+    fn test_oversized_parameters_are_clamped() {
+        let screen = Screen::new(5, 10);
+        for action in "ABCDEFGHJKLMPSTXbdefr@`a".chars() {
+            screen.feed(b"\x1b[3;5Habc");
+            for param in ["18446744073709551615", "99999999999999999999999", "65535"] {
+                screen.feed(format!("\x1b[{}{}", param, action).as_bytes());
+                screen.feed(format!("\x1b[{};{}{}", param, param, action).as_bytes());
+            }
+        }
+        // Still usable afterwards
+        screen.feed(b"\x1b[r\x1b[H\x1b[2Jok");
+        assert_eq!(screen.snapshot().lines[0], "ok");
+        assert_eq!(screen.cursor_position(), [2, 0]);
+    }
+
+    #[test]
+    fn test_alternate_screen() {
+        let s = screen(3, 10, "shell$ \x1b[?1049h\x1b[Hfull screen");
+        let snap = s.snapshot();
+        assert!(snap.alternate_screen);
+        assert_eq!(snap.lines[0], "full scree");
+
+        s.feed(b"\x1b[?1049l");
+        let snap = s.snapshot();
+        assert!(!snap.alternate_screen);
+        assert_eq!(snap.lines[0], "shell$");
+        assert_eq!(snap.cursor, [7, 0]);
+    }
+
+    #[test]
+    fn test_scroll_region_and_line_editing() {
+        // Status bar on the last row stays put while the region scrolls
+        let s = screen(4, 10, "\x1b[4;1Hstatus\x1b[1;3r\x1b[1;1Ha\r\nb\r\nc\r\nd");
+        assert_eq!(s.snapshot().lines, vec!["b", "c", "d", "status"]);
+
+        s.feed(b"\x1b[2;1H\x1b[L");
+        assert_eq!(s.snapshot().lines, vec!["b", "", "c", "status"]);
+        s.feed(b"\x1b[M");
+        assert_eq!(s.snapshot().lines, vec!["b", "c", "", "status"]);
+
+        s.feed(b"\x1b[1;1H\x1b[2@");
+        assert_eq!(s.snapshot().lines[0], "  b");
+        s.feed(b"\x1b[P");
+        assert_eq!(s.snapshot().lines[0], " b");
+    }
+
+    #[test]
+    fn test_attributes_and_strings_are_not_text() {
+        let s = screen(
+            2,
+            20,
+            "\x1b]0;title\x07\x1b[1;38;2;255;0;0mred\x1b[0m \x1b[2mghost",
+        );
+        let snap = s.snapshot();
+        assert_eq!(snap.lines[0], "red ghost");
+
+        let red = s.cell(0, 0).unwrap();
+        assert!(red.attrs.bold);
+        assert_eq!(red.attrs.fg, Color::Rgb(255, 0, 0));
+        assert!(s.cell(0, 4).unwrap().attrs.dim);
+        assert_eq!(s.cell(0, 3).unwrap().attrs, Attrs::default());
+    }
+
+    #[test]
+    fn test_split_sequences_and_wide_chars() {
+        let s = Screen::new(2, 10);
+        s.feed(b"\x1b[");
+        s.feed(b"31mok ");
+        s.feed(&"日本".as_bytes()[..4]);
+        s.feed(&"日本".as_bytes()[4..]);
+        let snap = s.snapshot();
+        assert_eq!(snap.lines[0], "ok 日本");
+        assert_eq!(snap.cursor, [7, 0]);
+    }
+
+    #[test]
+    fn test_cursor_rows() {
+        let s = Screen::new(6, 20);
+        assert_eq!(s.cursor_rows(1), None);
+
+        s.feed(b"reply\r\n\r\n> \r\n\r\nstatus\x1b[3;3H");
+        let rows = s.cursor_rows(1).unwrap();
+        assert_eq!(rows.before_cursor, "> ");
+        assert_eq!(rows.lines, vec!["", ">", ""]);
+        assert_eq!(s.cursor_rows(9).unwrap().lines.len(), 6);
+    }
+
+    #[test]
+    fn test_repaint_reproduces_screen() {
+        let s = screen(
+            5,
+            20,
+            "plain \x1b[1;31mred\x1b[0m \x1b[48;5;200mbg\x1b[0m\r\n\x1b[38;2;1;2;3m\u{65e5}\u{672c}\x1b[0m\x1b[2;4r\x1b[4;3H\x1b[?25l",
+        );
+        s.feed("\x1b[?1049h\x1b[Hfull".as_bytes());
+
+        let copy = Screen::new(5, 20);
+        copy.feed(&s.repaint());
+        assert_eq!(copy.snapshot(), s.snapshot());
+        for x in 0..20 {
+            assert_eq!(copy.cell(0, x), s.cell(0, x));
+        }
+
+        // Back on the primary screen, the attributes come through too
+        s.feed(b"\x1b[?1049l");
+        let copy = Screen::new(5, 20);
+        copy.feed(&s.repaint());
+        assert_eq!(copy.snapshot(), s.snapshot());
+        for (y, x) in [(0, 6), (0, 10), (1, 0), (1, 1)] {
+            assert_eq!(copy.cell(y, x), s.cell(y, x));
+        }
+    }
+
+    #[test]
+    fn test_resize_keeps_cursor_line() {
+        let s = screen(4, 10, "one\r\ntwo\r\nthree\r\n> ");
+        s.resize(2, 5);
+        let snap = s.snapshot();
+        assert_eq!(snap.lines, vec!["three", ">"]);
+        assert_eq!(snap.cursor, [2, 1]);
+        assert_eq!((snap.rows, snap.cols), (2, 5));
+    }
+}
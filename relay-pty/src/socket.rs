@@ -2,20 +2,48 @@
 //!
 //! Provides a socket interface at `/tmp/relay-pty-{name}.sock` or
 //! `/tmp/relay/{WORKSPACE_ID}/sockets/{name}.sock` that accepts:
+//! - Hello handshakes (protocol version and capabilities)
 //! - JSON-framed injection requests
+//! - Raw key input (named keys and literal text)
+//! - Queue listing and cancel/reprioritize requests for queued messages
 //! - Status queries
+//! - Output subscriptions
+//! - Read-only observers (live output after a repaint or scrollback replay)
+//! - Relay event subscriptions and acks
+//! - Review subscriptions and decisions (review mode)
+//! - Long polls that wait for the agent to go idle or for output to match a regex
 //! - Shutdown commands
 //!
 //! For injection requests, the connection stays open and streams all status
-//! updates (Queued → Injecting → Delivered/Failed) back to the client.
-
-use crate::protocol::{InjectRequest, InjectResponse, InjectStatus, QueuedMessage};
-use crate::queue::MessageQueue;
+//! updates (Queued → [Interrupting →] Injecting → Delivered/Failed, or Cancelled/Expired)
+//! back to the client.
+//! After a subscribe request, the connection also streams PTY output chunks;
+//! after subscribe_events, it streams sequenced relay events; after
+//! subscribe_reviews, it streams messages awaiting review. After observe,
+//! it streams raw output and refuses requests that would change anything.
+
+use crate::events::{EventLog, SequencedEvent};
+use crate::keys;
+use crate::modes::TerminalModes;
+use crate::output::{OutputHub, Utf8Decoder};
+use crate::parser::strip_ansi;
+use crate::protocol::{
+    IdleMethod, InjectOptions, InjectRequest, InjectResponse, InjectStatus, ObserveStart,
+    OutputFormat, QueuedMessage, ReviewAction, PROTOCOL_VERSION, SUPPORTED_REQUESTS,
+    SUPPORTED_RESPONSES,
+};
+use crate::queue::{MessageQueue, QueueError};
+use crate::review::{ReviewDecision, ReviewDesk};
+use crate::screen::Screen;
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use regex::Regex;
 use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
@@ -24,14 +52,33 @@ use tracing::{debug, error, info, warn};
 pub struct SocketServer {
     /// Path to the Unix socket
     socket_path: String,
+    /// State shared with every connection
+    ctx: SocketContext,
+}
+
+/// State shared by all socket connections
+#[derive(Clone)]
+pub struct SocketContext {
+    /// Agent name (reported by Hello)
+    pub agent_name: String,
     /// Message queue for injection
-    queue: Arc<MessageQueue>,
+    pub queue: Arc<MessageQueue>,
     /// Channel for status queries
-    status_tx: mpsc::Sender<StatusQuery>,
+    pub status_tx: mpsc::Sender<StatusQuery>,
     /// Shutdown signal
-    shutdown_tx: mpsc::Sender<()>,
+    pub shutdown_tx: mpsc::Sender<()>,
     /// Direct PTY write channel (for SendEnter)
-    pty_tx: mpsc::Sender<Vec<u8>>,
+    pub pty_tx: mpsc::Sender<Vec<u8>>,
+    /// PTY output fan-out (for Subscribe)
+    pub output: Arc<OutputHub>,
+    /// Sequenced relay events (for SubscribeEvents/Ack)
+    pub events: Arc<EventLog>,
+    /// Review desk (only in review mode)
+    pub review: Option<Arc<ReviewDesk>>,
+    /// Terminal modes the child has enabled (for SendKeys)
+    pub modes: Arc<TerminalModes>,
+    /// Headless model of the child's screen (for Screen)
+    pub screen: Arc<Screen>,
 }
 
 /// Status query request
@@ -43,101 +90,37 @@ pub struct StatusQuery {
 #[derive(Debug, Clone)]
 pub struct StatusInfo {
     pub agent_idle: bool,
+    pub idle_method: Option<IdleMethod>,
+    pub paused: bool,
+    pub user_active: bool,
     pub queue_length: usize,
+    pub ready: usize,
+    pub scheduled: usize,
+    pub max_size: usize,
+    pub seen_count: usize,
     pub cursor_position: Option<[u16; 2]>,
     pub last_output_ms: u64,
 }
 
 impl SocketServer {
     /// Create a new socket server
-    pub fn new(
-        socket_path: String,
-        queue: Arc<MessageQueue>,
-        status_tx: mpsc::Sender<StatusQuery>,
-        shutdown_tx: mpsc::Sender<()>,
-        pty_tx: mpsc::Sender<Vec<u8>>,
-    ) -> Self {
-        Self {
-            socket_path,
-            queue,
-            status_tx,
-            shutdown_tx,
-            pty_tx,
-        }
+    pub fn new(socket_path: String, ctx: SocketContext) -> Self {
+        Self { socket_path, ctx }
     }
 
     /// Start the socket server
     pub async fn run(self) -> Result<()> {
-        let path = Path::new(&self.socket_path);
-
-        // Create parent directory if needed (do this first)
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .context(format!("Failed to create socket directory {:?}", parent))?;
-        }
-
-        // Atomically remove existing socket - handles TOCTOU race condition
-        // Using remove_file directly instead of exists() check avoids race where
-        // another process removes the socket between our check and remove
-        match std::fs::remove_file(path) {
-            Ok(_) => debug!("Removed existing socket at {}", self.socket_path),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                // Socket doesn't exist - this is fine
-            }
-            Err(e) => {
-                warn!(
-                    "Failed to remove existing socket (will try bind anyway): {}",
-                    e
-                );
-            }
-        }
-
-        // Bind the socket with retry on EADDRINUSE
-        // This handles inherited file descriptor races when spawned from daemon
-        let listener = match UnixListener::bind(&self.socket_path) {
-            Ok(l) => l,
-            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
-                // Another process may have the socket - try removing again and retry
-                warn!(
-                    "Socket address in use, attempting cleanup and retry: {}",
-                    self.socket_path
-                );
-                let _ = std::fs::remove_file(path);
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                UnixListener::bind(&self.socket_path).context(format!(
-                    "Failed to bind socket at {} (after retry)",
-                    self.socket_path
-                ))?
-            }
-            Err(e) => {
-                return Err(e).context(format!("Failed to bind socket at {}", self.socket_path));
-            }
-        };
-
-        // Set socket permissions (0600 - owner only)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let perms = std::fs::Permissions::from_mode(0o600);
-            if let Err(e) = std::fs::set_permissions(&self.socket_path, perms) {
-                warn!("Failed to set socket permissions: {}", e);
-            }
-        }
+        let listener = bind(&self.socket_path)?;
 
         info!("Socket server listening at {}", self.socket_path);
 
         loop {
             match listener.accept().await {
                 Ok((stream, _)) => {
-                    let queue = Arc::clone(&self.queue);
-                    let status_tx = self.status_tx.clone();
-                    let shutdown_tx = self.shutdown_tx.clone();
-                    let pty_tx = self.pty_tx.clone();
+                    let ctx = self.ctx.clone();
 
                     tokio::spawn(async move {
-                        if let Err(e) =
-                            handle_connection(stream, queue, status_tx, shutdown_tx, pty_tx).await
-                        {
+                        if let Err(e) = handle_connection(stream, ctx).await {
                             error!("Connection error: {}", e);
                         }
                     });
@@ -150,27 +133,105 @@ impl SocketServer {
     }
 }
 
+/// Bind a Unix socket at `socket_path` (owner-only), replacing a stale one
+pub fn bind(socket_path: &str) -> Result<UnixListener> {
+    let path = Path::new(socket_path);
+
+    // Create parent directory if needed (do this first)
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .context(format!("Failed to create socket directory {:?}", parent))?;
+    }
+
+    // Atomically remove existing socket - handles TOCTOU race condition
+    // Using remove_file directly instead of exists() check avoids race where
+    // another process removes the socket between our check and remove
+    match std::fs::remove_file(path) {
+        Ok(_) => debug!("Removed existing socket at {}", socket_path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // Socket doesn't exist - this is fine
+        }
+        Err(e) => {
+            warn!(
+                "Failed to remove existing socket (will try bind anyway): {}",
+                e
+            );
+        }
+    }
+
+    // Bind the socket with retry on EADDRINUSE
+    // This handles inherited file descriptor races when spawned from daemon
+    let listener = match UnixListener::bind(socket_path) {
+        Ok(l) => l,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            // Another process may have the socket - try removing again and retry
+            warn!(
+                "Socket address in use, attempting cleanup and retry: {}",
+                socket_path
+            );
+            let _ = std::fs::remove_file(path);
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            UnixListener::bind(socket_path).context(format!(
+                "Failed to bind socket at {} (after retry)",
+                socket_path
+            ))?
+        }
+        Err(e) => {
+            return Err(e).context(format!("Failed to bind socket at {}", socket_path));
+        }
+    };
+
+    // Set socket permissions (0600 - owner only)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = std::fs::Permissions::from_mode(0o600);
+        if let Err(e) = std::fs::set_permissions(socket_path, perms) {
+            warn!("Failed to set socket permissions: {}", e);
+        }
+    }
+
+    Ok(listener)
+}
+
 /// Handle a single client connection
 ///
 /// For injection requests, this connection will stay open and stream all
-/// status updates until the final status (Delivered/Failed) is received.
-async fn handle_connection(
-    stream: UnixStream,
-    queue: Arc<MessageQueue>,
-    status_tx: mpsc::Sender<StatusQuery>,
-    shutdown_tx: mpsc::Sender<()>,
-    pty_tx: mpsc::Sender<Vec<u8>>,
-) -> Result<()> {
+/// status updates until the final status (Delivered/Failed/Cancelled/Expired) is received.
+/// Once subscribed, it also streams PTY output and/or relay events until the
+/// client disconnects.
+async fn handle_connection(stream: UnixStream, ctx: SocketContext) -> Result<()> {
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
 
     // Subscribe to response notifications
-    let mut response_rx = queue.subscribe_responses();
+    let mut response_rx = ctx.queue.subscribe_responses();
 
     // Track message IDs we're waiting for final responses on
     let mut pending_ids: HashSet<String> = HashSet::new();
 
+    // Delivered message IDs whose captured reply is still to come
+    let mut capture_ids: HashSet<String> = HashSet::new();
+
+    // PTY output subscription (set by a Subscribe request)
+    let mut output_rx: Option<broadcast::Receiver<Arc<[u8]>>> = None;
+    let mut output_format = OutputFormat::Raw;
+    let mut output_decoder = Utf8Decoder::new();
+    // Read-only observer (set by an Observe request)
+    let mut observer = false;
+
+    // Relay event subscription (set by a SubscribeEvents request)
+    let mut events_rx: Option<broadcast::Receiver<SequencedEvent>> = None;
+    // Highest event sequence number sent on this connection
+    let mut last_event_seq: u64 = 0;
+
+    // Messages awaiting review (set by a SubscribeReviews request: the reviewer role)
+    let mut review_rx: Option<broadcast::Receiver<QueuedMessage>> = None;
+
+    // Long polls in progress (WaitIdle / WaitForPattern)
+    let mut waits: FuturesUnordered<Wait> = FuturesUnordered::new();
+
     debug!("New client connection");
 
     loop {
@@ -192,19 +253,122 @@ async fn handle_connection(
 
                 // Parse JSON request
                 match serde_json::from_str::<InjectRequest>(trimmed) {
+                    Ok(request) if observer && !request.is_read_only() => {
+                        debug!("Refused {:?} from an observer", request);
+                        let response = InjectResponse::Error {
+                            message: "Observers are read-only".to_string(),
+                        };
+                        write_response(&mut writer, &response).await?;
+                    }
+                    Ok(InjectRequest::Observe { start, replay_bytes }) => {
+                        let (initial, rx) = observe(&ctx, start, replay_bytes);
+                        debug!("Client observing ({:?}, {} bytes to start)", start, initial.len());
+                        observer = true;
+                        output_rx = Some(rx);
+                        output_format = OutputFormat::Raw;
+                        output_decoder = Utf8Decoder::new();
+
+                        write_response(
+                            &mut writer,
+                            &InjectResponse::Observing {
+                                start,
+                                initial_bytes: initial.len(),
+                            },
+                        )
+                        .await?;
+                        if !initial.is_empty() {
+                            let chunk = output_chunk(&mut output_decoder, output_format, &initial);
+                            write_response(&mut writer, &chunk).await?;
+                        }
+                    }
+                    Ok(InjectRequest::Subscribe { format, replay_bytes }) => {
+                        // Subscriptions change connection state, so they are handled here
+                        // rather than in handle_request
+                        let (replay, rx) = ctx.output.subscribe(replay_bytes);
+                        debug!(
+                            "Client subscribed to output ({:?}, replaying {} bytes)",
+                            format,
+                            replay.len()
+                        );
+                        output_rx = Some(rx);
+                        output_format = format;
+                        output_decoder = Utf8Decoder::new();
+
+                        write_response(
+                            &mut writer,
+                            &InjectResponse::Subscribed {
+                                format,
+                                replayed_bytes: replay.len(),
+                            },
+                        )
+                        .await?;
+                        if !replay.is_empty() {
+                            let chunk = output_chunk(&mut output_decoder, format, &replay);
+                            write_response(&mut writer, &chunk).await?;
+                        }
+                    }
+                    Ok(InjectRequest::SubscribeEvents) => {
+                        let (replay, rx) = ctx.events.subscribe();
+                        debug!("Client subscribed to events (replaying {})", replay.len());
+                        events_rx = Some(rx);
+
+                        write_response(
+                            &mut writer,
+                            &InjectResponse::EventsSubscribed {
+                                replayed: replay.len(),
+                            },
+                        )
+                        .await?;
+                        for event in replay {
+                            last_event_seq = event.seq;
+                            write_response(&mut writer, &event_response(event)).await?;
+                        }
+                    }
+                    Ok(InjectRequest::SubscribeReviews) => {
+                        match ctx.review {
+                            Some(ref desk) => {
+                                let (current, rx) = desk.subscribe();
+                                debug!("Client subscribed to reviews");
+                                review_rx = Some(rx);
+
+                                write_response(&mut writer, &InjectResponse::ReviewsSubscribed).await?;
+                                if let Some(msg) = current {
+                                    write_response(&mut writer, &review_request(msg)).await?;
+                                }
+                            }
+                            None => write_response(&mut writer, &review_disabled()).await?,
+                        }
+                    }
+                    Ok(InjectRequest::Review { .. }) if review_rx.is_none() => {
+                        let response = InjectResponse::Error {
+                            message: "Only reviewers can review messages (send subscribe_reviews first)"
+                                .to_string(),
+                        };
+                        write_response(&mut writer, &response).await?;
+                    }
+                    Ok(request @ (InjectRequest::WaitIdle { .. } | InjectRequest::WaitForPattern { .. })) => {
+                        // Waits finish in the background so the connection keeps streaming
+                        match start_wait(request, &ctx) {
+                            Ok(wait) => waits.push(wait),
+                            Err(response) => write_response(&mut writer, &response).await?,
+                        }
+                    }
                     Ok(request) => {
                         // For inject requests, track the ID BEFORE calling handle_request
                         // This prevents a race where the "Queued" broadcast arrives before
                         // we've added the ID to pending_ids
-                        let inject_id = if let InjectRequest::Inject { ref id, .. } = request {
+                        let inject_id = if let InjectRequest::Inject { ref id, ref options, .. } = request {
                             debug!("Pre-tracking message {} for response streaming", id);
                             pending_ids.insert(id.clone());
+                            if options.capture_response {
+                                capture_ids.insert(id.clone());
+                            }
                             Some(id.clone())
                         } else {
                             None
                         };
 
-                        let response = handle_request(request, &queue, &status_tx, &shutdown_tx, &pty_tx).await;
+                        let response = handle_request(request, &ctx).await;
 
                         // Send the initial response to the client
                         // For inject requests, this is the "Queued" status
@@ -212,26 +376,18 @@ async fn handle_connection(
                         match (&response, &inject_id) {
                             (InjectResponse::InjectResult { .. }, Some(_)) => {
                                 // Send the Queued response immediately
-                                let response_json = serde_json::to_string(&response)?;
-                                writer.write_all(response_json.as_bytes()).await?;
-                                writer.write_all(b"\n").await?;
-                                writer.flush().await?;
+                                write_response(&mut writer, &response).await?;
                             }
                             (InjectResponse::Error { .. }, Some(id)) => {
                                 // Inject request failed - remove tracking and send error
                                 debug!("Inject request {} failed, removing tracking", id);
                                 pending_ids.remove(id);
-                                let response_json = serde_json::to_string(&response)?;
-                                writer.write_all(response_json.as_bytes()).await?;
-                                writer.write_all(b"\n").await?;
-                                writer.flush().await?;
+                                capture_ids.remove(id);
+                                write_response(&mut writer, &response).await?;
                             }
                             _ => {
                                 // Non-inject request - send response immediately
-                                let response_json = serde_json::to_string(&response)?;
-                                writer.write_all(response_json.as_bytes()).await?;
-                                writer.write_all(b"\n").await?;
-                                writer.flush().await?;
+                                write_response(&mut writer, &response).await?;
                             }
                         }
 
@@ -241,13 +397,7 @@ async fn handle_connection(
                         }
                     }
                     Err(e) => {
-                        let response = InjectResponse::Error {
-                            message: format!("Invalid JSON: {}", e),
-                        };
-                        let response_json = serde_json::to_string(&response)?;
-                        writer.write_all(response_json.as_bytes()).await?;
-                        writer.write_all(b"\n").await?;
-                        writer.flush().await?;
+                        write_response(&mut writer, &parse_error_response(trimmed, e)).await?;
                     }
                 }
 
@@ -270,25 +420,31 @@ async fn handle_connection(
 
                                 debug!("Forwarding response for message {}: {:?}", id, status);
 
-                                let response_json = serde_json::to_string(&response)?;
-                                writer.write_all(response_json.as_bytes()).await?;
-                                writer.write_all(b"\n").await?;
-                                writer.flush().await?;
+                                write_response(&mut writer, &response).await?;
 
                                 // Remove from pending if this is a final status
-                                if matches!(status, InjectStatus::Delivered | InjectStatus::Failed) {
+                                if status.is_final() {
                                     debug!("Message {} reached final state: {:?}", id, status);
                                     pending_ids.remove(id);
+                                    // Only a delivered message gets a captured reply
+                                    if !matches!(status, InjectStatus::Delivered) {
+                                        capture_ids.remove(id);
+                                    }
 
                                     // Clear from seen_ids immediately on delivery to free memory
                                     // This is critical for long-running sessions with 200+ agents
                                     if matches!(status, InjectStatus::Delivered) {
-                                        queue.mark_delivered(id).await;
+                                        ctx.queue.mark_delivered(id).await;
                                     }
                                     // Keep connection open for subsequent messages
                                     // Node.js orchestrator maintains a persistent socket
                                 }
                             }
+                        } else if let InjectResponse::CapturedResponse { ref id, .. } = response {
+                            if capture_ids.remove(id) {
+                                debug!("Forwarding captured reply for message {}", id);
+                                write_response(&mut writer, &response).await?;
+                            }
                         }
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
@@ -305,9 +461,349 @@ async fn handle_connection(
                     }
                 }
             }
+
+            // Stream PTY output to subscribed clients
+            result = recv_subscription(&mut output_rx) => {
+                match result {
+                    Ok(data) => {
+                        let chunk = output_chunk(&mut output_decoder, output_format, &data);
+                        write_response(&mut writer, &chunk).await?;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) if observer => {
+                        // Skip the backlog and redraw so the observer's view is whole again
+                        warn!("Observer lagged, {} chunks dropped, repainting", n);
+                        output_decoder = Utf8Decoder::new();
+                        let chunk = output_chunk(&mut output_decoder, output_format, &ctx.screen.repaint());
+                        write_response(&mut writer, &chunk).await?;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Output subscriber lagged, {} chunks dropped", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        debug!("Output channel closed");
+                        output_rx = None;
+                    }
+                }
+            }
+
+            // Send messages awaiting review to reviewers
+            result = recv_subscription(&mut review_rx) => {
+                match result {
+                    Ok(msg) => write_response(&mut writer, &review_request(msg)).await?,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Reviewer lagged by {} review requests", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        debug!("Review channel closed");
+                        review_rx = None;
+                    }
+                }
+            }
+
+            // Answer long polls as they finish
+            Some(response) = waits.next(), if !waits.is_empty() => {
+                write_response(&mut writer, &response).await?;
+            }
+
+            // Stream relay events to subscribed clients
+            result = recv_subscription(&mut events_rx) => {
+                match result {
+                    Ok(event) => {
+                        // Skip anything already sent during replay or gap recovery
+                        if event.seq > last_event_seq {
+                            last_event_seq = event.seq;
+                            write_response(&mut writer, &event_response(event)).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        // Live events were dropped, but unacked ones are still buffered
+                        warn!("Event subscriber lagged by {} events, resending from buffer", n);
+                        for event in ctx.events.since(last_event_seq) {
+                            last_event_seq = event.seq;
+                            write_response(&mut writer, &event_response(event)).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        debug!("Event channel closed");
+                        events_rx = None;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the response for a line that didn't parse as a request.
+///
+/// Well-formed JSON with a `type` this build doesn't know gets a structured
+/// `unsupported` response so newer clients can fall back gracefully;
+/// everything else is reported as invalid JSON.
+fn parse_error_response(line: &str, error: serde_json::Error) -> InjectResponse {
+    let request_type = serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|value| value.get("type")?.as_str().map(str::to_string));
+
+    match request_type {
+        Some(request_type) if !SUPPORTED_REQUESTS.contains(&request_type.as_str()) => {
+            InjectResponse::Unsupported {
+                message: format!("Unsupported request type: {}", request_type),
+                request_type,
+            }
+        }
+        _ => InjectResponse::Error {
+            message: format!("Invalid JSON: {}", error),
+        },
+    }
+}
+
+/// Receive from an optional broadcast subscription.
+/// Never resolves when there is no subscription, so it can sit in a `select!`.
+pub async fn recv_subscription<T: Clone>(
+    rx: &mut Option<broadcast::Receiver<T>>,
+) -> Result<T, broadcast::error::RecvError> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Build an output chunk response in the subscriber's format
+fn output_chunk(decoder: &mut Utf8Decoder, format: OutputFormat, data: &[u8]) -> InjectResponse {
+    let raw = decoder.decode(data);
+    let (data, text) = match format {
+        OutputFormat::Raw => (Some(raw), None),
+        OutputFormat::Stripped => (None, Some(strip_ansi(&raw))),
+        OutputFormat::Both => {
+            let text = strip_ansi(&raw);
+            (Some(raw), Some(text))
+        }
+    };
+
+    InjectResponse::Output {
+        data,
+        text,
+        timestamp: current_timestamp_ms(),
+    }
+}
+
+/// Subscribe an observer to live output, returning what brings it up to date
+fn observe(
+    ctx: &SocketContext,
+    start: ObserveStart,
+    replay_bytes: Option<usize>,
+) -> (Vec<u8>, broadcast::Receiver<Arc<[u8]>>) {
+    match start {
+        ObserveStart::Repaint => {
+            let (_, rx) = ctx.output.subscribe(0);
+            (ctx.screen.repaint(), rx)
+        }
+        ObserveStart::Scrollback => ctx.output.subscribe(replay_bytes.unwrap_or(usize::MAX)),
+    }
+}
+
+/// Wrap a sequenced event for the wire
+fn event_response(event: SequencedEvent) -> InjectResponse {
+    InjectResponse::Event {
+        seq: event.seq,
+        event: Box::new(event.event),
+    }
+}
+
+/// Build a review request for a message awaiting review
+fn review_request(msg: QueuedMessage) -> InjectResponse {
+    InjectResponse::ReviewRequest {
+        id: msg.id,
+        from: msg.from,
+        body: msg.body,
+        priority: msg.priority,
+        thread: msg.thread,
+    }
+}
+
+fn review_disabled() -> InjectResponse {
+    InjectResponse::Error {
+        message: "Review mode is not enabled (start relay-pty with --review)".to_string(),
+    }
+}
+
+/// Ask the main loop for the current status
+async fn query_status(status_tx: &mpsc::Sender<StatusQuery>) -> Result<StatusInfo, InjectResponse> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    if status_tx
+        .send(StatusQuery { response_tx: tx })
+        .await
+        .is_err()
+    {
+        return Err(InjectResponse::Error {
+            message: "Status channel closed".to_string(),
+        });
+    }
+    rx.await.map_err(|_| InjectResponse::Error {
+        message: "Failed to get status".to_string(),
+    })
+}
+
+/// Timeout for long polls that don't give one
+const DEFAULT_WAIT_TIMEOUT_MS: u64 = 60_000;
+
+/// How often `wait_idle` checks the agent's state
+const WAIT_IDLE_POLL_MS: u64 = 100;
+
+/// Most recent output searched by `wait_for_pattern`
+const WAIT_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// A long poll in progress, resolving to its `wait_result`
+type Wait = BoxFuture<'static, InjectResponse>;
+
+/// Start a `wait_idle` or `wait_for_pattern` request. Output is watched from
+/// this call on, so nothing is missed before the wait is first polled.
+fn start_wait(request: InjectRequest, ctx: &SocketContext) -> Result<Wait, InjectResponse> {
+    match request {
+        InjectRequest::WaitIdle {
+            id,
+            timeout_ms,
+            after_busy,
+        } => {
+            let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_WAIT_TIMEOUT_MS));
+            debug!(
+                "Waiting up to {:?} for the agent to go idle (after busy: {})",
+                timeout, after_busy
+            );
+            Ok(Box::pin(wait_idle(
+                ctx.status_tx.clone(),
+                id,
+                timeout,
+                after_busy,
+            )))
+        }
+        InjectRequest::WaitForPattern {
+            id,
+            pattern,
+            timeout_ms,
+        } => {
+            let regex = Regex::new(&pattern).map_err(|e| InjectResponse::Error {
+                message: format!("Invalid pattern {:?}: {}", pattern, e),
+            })?;
+            let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_WAIT_TIMEOUT_MS));
+            debug!(
+                "Waiting up to {:?} for output matching /{}/",
+                timeout, regex
+            );
+            let (_, rx) = ctx.output.subscribe(0);
+            Ok(Box::pin(wait_for_pattern(rx, id, regex, timeout)))
+        }
+        other => Err(InjectResponse::Error {
+            message: format!("Not a wait request: {:?}", other),
+        }),
+    }
+}
+
+/// Poll the agent's idle state until it is idle or `timeout` passes.
+/// With `after_busy`, idle only counts once a poll has seen it busy.
+async fn wait_idle(
+    status_tx: mpsc::Sender<StatusQuery>,
+    id: Option<String>,
+    timeout: Duration,
+    after_busy: bool,
+) -> InjectResponse {
+    let start = Instant::now();
+    let mut seen_busy = !after_busy;
+    loop {
+        match query_status(&status_tx).await {
+            Ok(info) if info.agent_idle && seen_busy => return wait_result(id, start, Ok(None)),
+            Ok(info) if !info.agent_idle => seen_busy = true,
+            Ok(_) => {}
+            Err(response) => return response,
+        }
+        if start.elapsed() >= timeout {
+            let reason = if seen_busy {
+                format!("Agent still busy after {}ms", timeout.as_millis())
+            } else {
+                format!("Agent never went busy within {}ms", timeout.as_millis())
+            };
+            return wait_result(id, start, Err(reason));
+        }
+        tokio::time::sleep(Duration::from_millis(WAIT_IDLE_POLL_MS)).await;
+    }
+}
+
+/// Match new output (ANSI-stripped) against `pattern` until it matches or
+/// `timeout` passes
+async fn wait_for_pattern(
+    mut output_rx: broadcast::Receiver<Arc<[u8]>>,
+    id: Option<String>,
+    pattern: Regex,
+    timeout: Duration,
+) -> InjectResponse {
+    let start = Instant::now();
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    let mut recent: Vec<u8> = Vec::new();
+
+    loop {
+        tokio::select! {
+            result = output_rx.recv() => match result {
+                Ok(chunk) => {
+                    recent.extend_from_slice(&chunk);
+                    if recent.len() > WAIT_OUTPUT_BYTES {
+                        recent.drain(..recent.len() - WAIT_OUTPUT_BYTES);
+                    }
+                    let text = strip_ansi(&String::from_utf8_lossy(&recent));
+                    if let Some(found) = pattern.find(&text) {
+                        return wait_result(id, start, Ok(Some(found.as_str().to_string())));
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Pattern wait lagged, {} output chunks skipped", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return wait_result(id, start, Err("Agent output ended".to_string()));
+                }
+            },
+            _ = &mut deadline => {
+                let reason = format!("No output matched /{}/ within {}ms", pattern, timeout.as_millis());
+                return wait_result(id, start, Err(reason));
+            }
         }
     }
+}
+
+/// Build a `wait_result`: matched (with any matching text) or the reason it wasn't
+fn wait_result(
+    id: Option<String>,
+    start: Instant,
+    outcome: Result<Option<String>, String>,
+) -> InjectResponse {
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+    match outcome {
+        Ok(text) => InjectResponse::WaitResult {
+            id,
+            matched: true,
+            text,
+            reason: None,
+            elapsed_ms,
+        },
+        Err(reason) => InjectResponse::WaitResult {
+            id,
+            matched: false,
+            text: None,
+            reason: Some(reason),
+            elapsed_ms,
+        },
+    }
+}
 
+/// Write a single JSON-line response to the client
+async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: &InjectResponse,
+) -> Result<()> {
+    let response_json = serde_json::to_string(response)?;
+    writer.write_all(response_json.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
     Ok(())
 }
 
@@ -315,27 +811,52 @@ async fn handle_connection(
 ///
 /// For inject requests, returns None on success (queue broadcasts the response),
 /// or an Error response if the message was rejected.
-async fn handle_request(
-    request: InjectRequest,
-    queue: &Arc<MessageQueue>,
-    status_tx: &mpsc::Sender<StatusQuery>,
-    shutdown_tx: &mpsc::Sender<()>,
-    pty_tx: &mpsc::Sender<Vec<u8>>,
-) -> InjectResponse {
+async fn handle_request(request: InjectRequest, ctx: &SocketContext) -> InjectResponse {
     match request {
+        InjectRequest::Hello {
+            protocol_version,
+            client,
+        } => {
+            debug!(
+                "Hello from {} (protocol {:?})",
+                client.as_deref().unwrap_or("unknown client"),
+                protocol_version
+            );
+            if protocol_version.is_some_and(|v| v > PROTOCOL_VERSION) {
+                warn!(
+                    "Client speaks protocol {:?}, newer than ours ({})",
+                    protocol_version, PROTOCOL_VERSION
+                );
+            }
+
+            InjectResponse::HelloAck {
+                protocol_version: PROTOCOL_VERSION,
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                agent: ctx.agent_name.clone(),
+                requests: SUPPORTED_REQUESTS.iter().map(|s| s.to_string()).collect(),
+                responses: SUPPORTED_RESPONSES.iter().map(|s| s.to_string()).collect(),
+            }
+        }
+
         InjectRequest::Inject {
             id,
             from,
             body,
             priority,
+            options,
         } => {
             debug!(
-                "Inject request: {} from {} (priority {})",
-                id, from, priority
+                "Inject request: {} from {} (priority {}, {:?})",
+                id, from, priority, options
             );
 
-            let msg = QueuedMessage::new(id.clone(), from, body, priority);
-            let queued = queue.enqueue(msg).await;
+            let mut msg = QueuedMessage::new(id.clone(), from, body, priority);
+            msg.expires_at = options.deadline();
+            msg.not_before = options.not_before();
+            msg.sync = options.sync();
+            msg.interrupt = options.interrupt;
+            msg.thread = options.thread;
+            let queued = ctx.queue.enqueue(msg).await;
 
             if queued {
                 // Success - the queue will broadcast the Queued status,
@@ -359,7 +880,7 @@ async fn handle_request(
             info!("SendEnter request for message {}", id);
 
             // Send just the Enter key (\r) to the PTY
-            let success = pty_tx.send(vec![0x0d]).await.is_ok();
+            let success = ctx.pty_tx.send(vec![0x0d]).await.is_ok();
 
             if success {
                 info!("Enter key sent successfully for {}", id);
@@ -374,35 +895,175 @@ async fn handle_request(
             }
         }
 
-        InjectRequest::Status => {
-            let (tx, rx) = tokio::sync::oneshot::channel();
+        InjectRequest::SendKeys { id, keys } => {
+            let bytes = match keys::encode(&keys, ctx.modes.application_cursor()) {
+                Ok(bytes) if bytes.is_empty() => {
+                    return InjectResponse::Error {
+                        message: "No keys to send".to_string(),
+                    }
+                }
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    return InjectResponse::Error {
+                        message: e.to_string(),
+                    }
+                }
+            };
+            let len = bytes.len();
+            // The keys may be secrets typed on someone's behalf: details only at debug
+            info!("SendKeys request: {} bytes", len);
+            debug!("SendKeys keys: {:?}", keys);
+
+            let success = ctx.pty_tx.send(bytes).await.is_ok();
+            if !success {
+                warn!("Failed to send keys - PTY channel closed");
+            }
 
-            if status_tx
-                .send(StatusQuery { response_tx: tx })
-                .await
-                .is_ok()
-            {
-                match rx.await {
-                    Ok(info) => InjectResponse::Status {
-                        agent_idle: info.agent_idle,
-                        queue_length: info.queue_length,
-                        cursor_position: info.cursor_position,
-                        last_output_ms: info.last_output_ms,
-                    },
-                    Err(_) => InjectResponse::Error {
-                        message: "Failed to get status".to_string(),
-                    },
+            InjectResponse::SendKeysResult {
+                id,
+                success,
+                bytes: len,
+                timestamp: current_timestamp_ms(),
+            }
+        }
+
+        InjectRequest::Cancel { id } => {
+            let mut result = ctx.queue.cancel(&id).await.map(|_| ());
+            // A message awaiting review has left the queue but isn't injecting yet
+            if let (Err(QueueError::Injecting(_)), Some(desk)) = (&result, &ctx.review) {
+                if desk.withdraw(&id) {
+                    ctx.queue.forget(&id).await;
+                    ctx.queue.report_result(
+                        id.clone(),
+                        InjectStatus::Cancelled,
+                        Some("Cancelled during review".to_string()),
+                    );
+                    result = Ok(());
                 }
-            } else {
-                InjectResponse::Error {
-                    message: "Status channel closed".to_string(),
+            }
+            match result {
+                Ok(()) => InjectResponse::CancelResult {
+                    id,
+                    timestamp: current_timestamp_ms(),
+                },
+                Err(e) => InjectResponse::Error {
+                    message: e.to_string(),
+                },
+            }
+        }
+
+        InjectRequest::Reprioritize { id, priority } => {
+            match ctx.queue.reprioritize(&id, priority).await {
+                Ok(()) => InjectResponse::ReprioritizeResult {
+                    id,
+                    priority,
+                    timestamp: current_timestamp_ms(),
+                },
+                Err(e) => InjectResponse::Error {
+                    message: e.to_string(),
+                },
+            }
+        }
+
+        InjectRequest::ListQueue => InjectResponse::QueueList {
+            messages: ctx
+                .queue
+                .pending()
+                .await
+                .iter()
+                .map(QueuedMessage::queue_entry)
+                .collect(),
+        },
+
+        InjectRequest::Status => match query_status(&ctx.status_tx).await {
+            Ok(info) => InjectResponse::Status {
+                agent_idle: info.agent_idle,
+                idle_method: info.idle_method,
+                paused: info.paused,
+                user_active: info.user_active,
+                queue_length: info.queue_length,
+                ready: info.ready,
+                scheduled: info.scheduled,
+                max_size: info.max_size,
+                seen_count: info.seen_count,
+                cursor_position: info.cursor_position,
+                last_output_ms: info.last_output_ms,
+            },
+            Err(response) => response,
+        },
+
+        InjectRequest::Screen => {
+            let snapshot = ctx.screen.snapshot();
+            InjectResponse::Screen {
+                rows: snapshot.rows,
+                cols: snapshot.cols,
+                cursor: snapshot.cursor,
+                cursor_visible: snapshot.cursor_visible,
+                alternate_screen: snapshot.alternate_screen,
+                lines: snapshot.lines,
+            }
+        }
+
+        InjectRequest::WaitIdle { .. } | InjectRequest::WaitForPattern { .. } => {
+            InjectResponse::Error {
+                message: "Waits are started by the connection".to_string(),
+            }
+        }
+
+        InjectRequest::Subscribe { .. }
+        | InjectRequest::Observe { .. }
+        | InjectRequest::SubscribeEvents
+        | InjectRequest::SubscribeReviews => InjectResponse::Error {
+            message: "Subscriptions must be sent on a streaming connection".to_string(),
+        },
+
+        InjectRequest::Review {
+            id,
+            decision,
+            body,
+            reason,
+        } => {
+            let Some(ref desk) = ctx.review else {
+                return review_disabled();
+            };
+            let review_decision = match decision {
+                ReviewAction::Approve => ReviewDecision::Approve,
+                ReviewAction::Edit => match body {
+                    Some(body) => ReviewDecision::Edit(body),
+                    None => {
+                        return InjectResponse::Error {
+                            message: "An edit review needs a body".to_string(),
+                        }
+                    }
+                },
+                ReviewAction::Reject => {
+                    ReviewDecision::Reject(reason.unwrap_or_else(|| "no reason given".to_string()))
                 }
+            };
+            match desk.decide(&id, review_decision) {
+                Ok(()) => InjectResponse::ReviewResult {
+                    id,
+                    decision,
+                    timestamp: current_timestamp_ms(),
+                },
+                Err(e) => InjectResponse::Error {
+                    message: e.to_string(),
+                },
+            }
+        }
+
+        InjectRequest::Ack { seq } => {
+            let released = ctx.events.ack(seq);
+            InjectResponse::AckResult {
+                seq,
+                released,
+                pending: ctx.events.pending(),
             }
         }
 
         InjectRequest::Shutdown => {
             info!("Shutdown requested via socket");
-            let _ = shutdown_tx.send(()).await;
+            let _ = ctx.shutdown_tx.send(()).await;
             InjectResponse::ShutdownAck
         }
     }
@@ -439,6 +1100,7 @@ impl SocketClient {
             from,
             body,
             priority,
+            options: InjectOptions::default(),
         })
         .await
     }
@@ -479,32 +1141,60 @@ impl SocketClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::InjectStatus;
+    use crate::protocol::{ContinuityCommand, InjectStatus, ParsedRelayCommand, RelayEvent};
     use tempfile::tempdir;
+    use tokio::io::AsyncBufRead;
     use tokio::sync::broadcast;
 
-    #[tokio::test]
-    async fn test_socket_server_client() {
-        let dir = tempdir().unwrap();
-        let socket_path = dir.path().join("test.sock").to_string_lossy().to_string();
+    /// Channels on the far side of a test context
+    struct TestChannels {
+        status_rx: mpsc::Receiver<StatusQuery>,
+        shutdown_rx: mpsc::Receiver<()>,
+        pty_rx: mpsc::Receiver<Vec<u8>>,
+    }
 
+    fn test_context(queue_size: usize) -> (SocketContext, TestChannels) {
         let (response_tx, _response_rx) = broadcast::channel(16);
-        let (status_tx, _status_rx) = mpsc::channel(16);
-        let (shutdown_tx, _shutdown_rx) = mpsc::channel(1);
-        let (pty_tx, _pty_rx) = mpsc::channel::<Vec<u8>>(16);
-
-        let queue = Arc::new(MessageQueue::new(10, response_tx));
+        let (status_tx, status_rx) = mpsc::channel(16);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        let (pty_tx, pty_rx) = mpsc::channel(16);
 
-        let server = SocketServer::new(
-            socket_path.clone(),
-            Arc::clone(&queue),
+        let ctx = SocketContext {
+            agent_name: "TestAgent".to_string(),
+            queue: Arc::new(MessageQueue::new(queue_size, response_tx)),
             status_tx,
             shutdown_tx,
             pty_tx,
-        );
+            output: Arc::new(OutputHub::new(1024)),
+            events: Arc::new(EventLog::new(16)),
+            review: None,
+            modes: Arc::new(TerminalModes::new()),
+            screen: Arc::new(Screen::new(24, 80)),
+        };
+        let channels = TestChannels {
+            status_rx,
+            shutdown_rx,
+            pty_rx,
+        };
+        (ctx, channels)
+    }
 
-        // Start server in background
-        let server_handle = tokio::spawn(async move {
+    async fn read_response<R: AsyncBufRead + Unpin>(reader: &mut R) -> InjectResponse {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        serde_json::from_str(line.trim()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_socket_server_client() {
+        let dir = tempdir().unwrap();
+        let socket_path = dir.path().join("test.sock").to_string_lossy().to_string();
+
+        let (ctx, _channels) = test_context(10);
+        let server = SocketServer::new(socket_path.clone(), ctx);
+
+        // Start server in background
+        let server_handle = tokio::spawn(async move {
             server.run().await.ok();
         });
 
@@ -533,22 +1223,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_status_channel_closed() {
-        let (response_tx, _response_rx) = broadcast::channel(1);
-        let (status_tx, status_rx) = mpsc::channel(1);
-        drop(status_rx);
-        let (shutdown_tx, _shutdown_rx) = mpsc::channel(1);
-        let (pty_tx, _pty_rx) = mpsc::channel(1);
-
-        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let (ctx, channels) = test_context(1);
+        drop(channels.status_rx);
 
-        let response = handle_request(
-            InjectRequest::Status,
-            &queue,
-            &status_tx,
-            &shutdown_tx,
-            &pty_tx,
-        )
-        .await;
+        let response = handle_request(InjectRequest::Status, &ctx).await;
 
         match response {
             InjectResponse::Error { message } => {
@@ -560,39 +1238,24 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_request_shutdown() {
-        let (response_tx, _response_rx) = broadcast::channel(1);
-        let (status_tx, _status_rx) = mpsc::channel(1);
-        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
-        let (pty_tx, _pty_rx) = mpsc::channel(1);
-
-        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let (ctx, mut channels) = test_context(1);
 
-        let response = handle_request(
-            InjectRequest::Shutdown,
-            &queue,
-            &status_tx,
-            &shutdown_tx,
-            &pty_tx,
-        )
-        .await;
+        let response = handle_request(InjectRequest::Shutdown, &ctx).await;
         assert!(matches!(response, InjectResponse::ShutdownAck));
 
-        let received =
-            tokio::time::timeout(tokio::time::Duration::from_millis(200), shutdown_rx.recv())
-                .await
-                .ok()
-                .flatten();
+        let received = tokio::time::timeout(
+            tokio::time::Duration::from_millis(200),
+            channels.shutdown_rx.recv(),
+        )
+        .await
+        .ok()
+        .flatten();
         assert!(received.is_some());
     }
 
     #[tokio::test]
     async fn test_handle_request_duplicate_inject() {
-        let (response_tx, _response_rx) = broadcast::channel(4);
-        let (status_tx, _status_rx) = mpsc::channel(1);
-        let (shutdown_tx, _shutdown_rx) = mpsc::channel(1);
-        let (pty_tx, _pty_rx) = mpsc::channel(1);
-
-        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let (ctx, _channels) = test_context(1);
 
         let first = handle_request(
             InjectRequest::Inject {
@@ -600,11 +1263,9 @@ mod tests {
                 from: "Alice".to_string(),
                 body: "Hello".to_string(),
                 priority: 0,
+                options: InjectOptions::default(),
             },
-            &queue,
-            &status_tx,
-            &shutdown_tx,
-            &pty_tx,
+            &ctx,
         )
         .await;
 
@@ -622,11 +1283,9 @@ mod tests {
                 from: "Alice".to_string(),
                 body: "Hello again".to_string(),
                 priority: 0,
+                options: InjectOptions::default(),
             },
-            &queue,
-            &status_tx,
-            &shutdown_tx,
-            &pty_tx,
+            &ctx,
         )
         .await;
 
@@ -647,18 +1306,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_connection_invalid_json() {
-        let (response_tx, _response_rx) = broadcast::channel(1);
-        let (status_tx, _status_rx) = mpsc::channel(1);
-        let (shutdown_tx, _shutdown_rx) = mpsc::channel(1);
-        let (pty_tx, _pty_rx) = mpsc::channel::<Vec<u8>>(1);
-
-        let queue = Arc::new(MessageQueue::new(1, response_tx));
+        let (ctx, _channels) = test_context(1);
         let (server_stream, client_stream) = UnixStream::pair().unwrap();
 
         let server_handle = tokio::spawn(async move {
-            handle_connection(server_stream, queue, status_tx, shutdown_tx, pty_tx)
-                .await
-                .unwrap();
+            handle_connection(server_stream, ctx).await.unwrap();
         });
 
         let (reader, mut writer) = client_stream.into_split();
@@ -680,4 +1332,792 @@ mod tests {
         drop(writer);
         server_handle.abort();
     }
+
+    #[tokio::test]
+    async fn test_handle_connection_subscribe_streams_output() {
+        let (ctx, _channels) = test_context(1);
+        let output = Arc::clone(&ctx.output);
+        output.publish(b"earlier \x1b[1moutput\x1b[0m");
+
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server_handle = tokio::spawn(async move {
+            handle_connection(server_stream, ctx).await.unwrap();
+        });
+
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"type\":\"subscribe\",\"format\":\"both\",\"replay_bytes\":6}\n")
+            .await
+            .unwrap();
+        writer.flush().await.unwrap();
+
+        match read_response(&mut reader).await {
+            InjectResponse::Subscribed {
+                format,
+                replayed_bytes,
+            } => {
+                assert_eq!(format, OutputFormat::Both);
+                assert_eq!(replayed_bytes, 6);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        match read_response(&mut reader).await {
+            InjectResponse::Output { data, text, .. } => {
+                assert_eq!(data.as_deref(), Some("ut\x1b[0m"));
+                assert_eq!(text.as_deref(), Some("ut"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        output.publish(b"\x1b[32mlive\x1b[0m");
+        match read_response(&mut reader).await {
+            InjectResponse::Output { data, text, .. } => {
+                assert_eq!(data.as_deref(), Some("\x1b[32mlive\x1b[0m"));
+                assert_eq!(text.as_deref(), Some("live"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        drop(writer);
+        server_handle.abort();
+    }
+
+    async fn send_line<W: AsyncWrite + Unpin>(writer: &mut W, line: &str) {
+        writer.write_all(line.as_bytes()).await.unwrap();
+        writer.write_all(b"\n").await.unwrap();
+        writer.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_observers_are_read_only() {
+        let (ctx, _channels) = test_context(1);
+        let output = Arc::clone(&ctx.output);
+        output.publish(b"earlier");
+        ctx.screen.feed(b"> hello");
+
+        let mut observers = Vec::new();
+        for request in [
+            r#"{"type":"observe"}"#,
+            r#"{"type":"observe","start":"scrollback"}"#,
+        ] {
+            let (server_stream, client_stream) = UnixStream::pair().unwrap();
+            let ctx = ctx.clone();
+            tokio::spawn(async move { handle_connection(server_stream, ctx).await });
+            let (reader, mut writer) = client_stream.into_split();
+            send_line(&mut writer, request).await;
+            observers.push((BufReader::new(reader), writer));
+        }
+
+        // The first observer starts from a repaint of the screen
+        let (ref mut reader, ref mut writer) = observers[0];
+        assert!(matches!(
+            read_response(reader).await,
+            InjectResponse::Observing {
+                start: ObserveStart::Repaint,
+                ..
+            }
+        ));
+        match read_response(reader).await {
+            InjectResponse::Output { data, .. } => {
+                let copy = Screen::new(24, 80);
+                copy.feed(data.unwrap().as_bytes());
+                assert_eq!(copy.snapshot().lines[0], "> hello");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // Observers can look but not touch
+        send_line(writer, r#"{"type":"send_enter","id":"msg-1"}"#).await;
+        match read_response(reader).await {
+            InjectResponse::Error { message } => assert!(message.contains("read-only")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        send_line(writer, r#"{"type":"screen"}"#).await;
+        assert!(matches!(
+            read_response(reader).await,
+            InjectResponse::Screen { .. }
+        ));
+
+        // The second one starts from the scrollback
+        let (ref mut reader, _) = observers[1];
+        assert!(matches!(
+            read_response(reader).await,
+            InjectResponse::Observing {
+                start: ObserveStart::Scrollback,
+                initial_bytes: 7
+            }
+        ));
+        match read_response(reader).await {
+            InjectResponse::Output { data, .. } => assert_eq!(data.as_deref(), Some("earlier")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // Both see live output
+        output.publish(b" live");
+        for (reader, _) in observers.iter_mut() {
+            match read_response(reader).await {
+                InjectResponse::Output { data, .. } => assert_eq!(data.as_deref(), Some(" live")),
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_lagging_observer_is_repainted() {
+        let (ctx, _channels) = test_context(1);
+        let output = Arc::clone(&ctx.output);
+        let screen = Arc::clone(&ctx.screen);
+        screen.feed(b"> hello");
+
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        tokio::spawn(async move { handle_connection(server_stream, ctx).await });
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+        send_line(&mut writer, r#"{"type":"observe","start":"scrollback"}"#).await;
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::Observing { .. }
+        ));
+
+        // Far more output than the observer's buffer holds, published without
+        // waiting for it
+        for _ in 0..1000 {
+            output.publish(b"x");
+        }
+
+        // It skips ahead to a repaint, then follows live output again
+        match read_response(&mut reader).await {
+            InjectResponse::Output { data, .. } => {
+                assert_eq!(data.unwrap().into_bytes(), screen.repaint());
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        match read_response(&mut reader).await {
+            InjectResponse::Output { data, .. } => assert_eq!(data.as_deref(), Some("x")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_events_replay_until_acked() {
+        let (ctx, _channels) = test_context(1);
+        let events = Arc::clone(&ctx.events);
+        events.push(RelayEvent::Command(ParsedRelayCommand::new_spawn(
+            "Lead".to_string(),
+            "Worker".to_string(),
+            "claude".to_string(),
+            "Do the thing".to_string(),
+            "->relay-file:spawn".to_string(),
+        )));
+
+        // First connection sees the buffered event and acks it
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server_handle = tokio::spawn(async move {
+            handle_connection(server_stream, ctx).await.unwrap();
+        });
+
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"type\":\"subscribe_events\"}\n")
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::EventsSubscribed { replayed: 1 }
+        ));
+        match read_response(&mut reader).await {
+            InjectResponse::Event { seq, event } => {
+                assert_eq!(seq, 1);
+                match *event {
+                    RelayEvent::Command(cmd) => assert_eq!(cmd.kind, "spawn"),
+                    other => panic!("Unexpected event: {:?}", other),
+                }
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // Live events follow the replay
+        events.push(RelayEvent::Continuity(ContinuityCommand::new(
+            "save".to_string(),
+            "notes".to_string(),
+        )));
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::Event { seq: 2, .. }
+        ));
+
+        writer
+            .write_all(b"{\"type\":\"ack\",\"seq\":1}\n")
+            .await
+            .unwrap();
+        match read_response(&mut reader).await {
+            InjectResponse::AckResult {
+                seq,
+                released,
+                pending,
+            } => {
+                assert_eq!(seq, 1);
+                assert_eq!(released, 1);
+                assert_eq!(pending, 1);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        drop(writer);
+        server_handle.abort();
+
+        // Only the unacked event is replayed to the next subscriber
+        let (replay, _rx) = events.subscribe();
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].seq, 2);
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_hello() {
+        let (ctx, _channels) = test_context(1);
+
+        let response = handle_request(
+            InjectRequest::Hello {
+                protocol_version: Some(PROTOCOL_VERSION),
+                client: Some("test".to_string()),
+            },
+            &ctx,
+        )
+        .await;
+
+        match response {
+            InjectResponse::HelloAck {
+                protocol_version,
+                version,
+                agent,
+                requests,
+                responses,
+            } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(version, env!("CARGO_PKG_VERSION"));
+                assert_eq!(agent, "TestAgent");
+                assert!(requests.iter().any(|r| r == "inject"));
+                assert!(responses.iter().any(|r| r == "unsupported"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_unknown_request_type() {
+        let (ctx, _channels) = test_context(1);
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+
+        let server_handle = tokio::spawn(async move {
+            handle_connection(server_stream, ctx).await.unwrap();
+        });
+
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"type\":\"teleport\",\"to\":\"mars\"}\n")
+            .await
+            .unwrap();
+        match read_response(&mut reader).await {
+            InjectResponse::Unsupported { request_type, .. } => {
+                assert_eq!(request_type, "teleport");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // A known type with bad fields is still a plain error
+        writer
+            .write_all(b"{\"type\":\"ack\",\"seq\":\"x\"}\n")
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::Error { .. }
+        ));
+
+        drop(writer);
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_cancel_and_reprioritize() {
+        let (ctx, _channels) = test_context(4);
+
+        handle_request(
+            InjectRequest::Inject {
+                id: "msg-1".to_string(),
+                from: "Alice".to_string(),
+                body: "Hello".to_string(),
+                priority: 5,
+                options: InjectOptions::default(),
+            },
+            &ctx,
+        )
+        .await;
+
+        assert!(matches!(
+            handle_request(
+                InjectRequest::Reprioritize {
+                    id: "msg-1".to_string(),
+                    priority: 0,
+                },
+                &ctx,
+            )
+            .await,
+            InjectResponse::ReprioritizeResult { priority: 0, .. }
+        ));
+        assert!(matches!(
+            handle_request(
+                InjectRequest::Cancel {
+                    id: "msg-1".to_string()
+                },
+                &ctx
+            )
+            .await,
+            InjectResponse::CancelResult { .. }
+        ));
+
+        match handle_request(
+            InjectRequest::Cancel {
+                id: "msg-1".to_string(),
+            },
+            &ctx,
+        )
+        .await
+        {
+            InjectResponse::Error { message } => {
+                assert!(message.contains("already cancelled"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_list_queue() {
+        let (ctx, _channels) = test_context(4);
+
+        for (id, priority) in [("later", 5), ("sooner", 0)] {
+            handle_request(
+                InjectRequest::Inject {
+                    id: id.to_string(),
+                    from: "Alice".to_string(),
+                    body: format!("{} body", id),
+                    priority,
+                    options: InjectOptions::default(),
+                },
+                &ctx,
+            )
+            .await;
+        }
+
+        match handle_request(InjectRequest::ListQueue, &ctx).await {
+            InjectResponse::QueueList { messages } => {
+                let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+                assert_eq!(ids, vec!["sooner", "later"]);
+                assert_eq!(messages[0].from, "Alice");
+                assert_eq!(messages[0].preview, "sooner body");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_withdraws_message_awaiting_review() {
+        let (mut ctx, _channels) = test_context(4);
+        let desk = Arc::new(ReviewDesk::new());
+        ctx.review = Some(Arc::clone(&desk));
+        let mut results = ctx.queue.subscribe_responses();
+
+        handle_request(
+            InjectRequest::Inject {
+                id: "msg-1".to_string(),
+                from: "Alice".to_string(),
+                body: "Deploy?".to_string(),
+                priority: 0,
+                options: InjectOptions::default(),
+            },
+            &ctx,
+        )
+        .await;
+        // The injector takes it off the queue and hands it to the desk
+        let msg = ctx.queue.wait_and_dequeue().await;
+        let review_task = {
+            let desk = Arc::clone(&desk);
+            tokio::spawn(async move { desk.review(msg).await })
+        };
+        while desk.pending().is_none() {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        assert!(matches!(
+            handle_request(
+                InjectRequest::Cancel {
+                    id: "msg-1".to_string()
+                },
+                &ctx
+            )
+            .await,
+            InjectResponse::CancelResult { .. }
+        ));
+        assert_eq!(review_task.await.unwrap(), None);
+        loop {
+            if let InjectResponse::InjectResult { status, .. } = results.recv().await.unwrap() {
+                if status.is_final() {
+                    assert_eq!(status, InjectStatus::Cancelled);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_review_requires_reviewer_role() {
+        let dir = tempdir().unwrap();
+        let socket_path = dir.path().join("review.sock");
+        let (mut ctx, _channels) = test_context(10);
+        let desk = Arc::new(ReviewDesk::new());
+        ctx.review = Some(Arc::clone(&desk));
+
+        let server = SocketServer::new(socket_path.to_string_lossy().into_owned(), ctx);
+        tokio::spawn(server.run());
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let stream = UnixStream::connect(&socket_path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let review = r#"{"type":"review","id":"msg-1","decision":"approve"}"#;
+        writer
+            .write_all(format!("{}\n", review).as_bytes())
+            .await
+            .unwrap();
+        match read_response(&mut reader).await {
+            InjectResponse::Error { message } => assert!(message.contains("subscribe_reviews")),
+            other => panic!("Expected error, got {:?}", other),
+        }
+
+        // A message already awaiting review is sent right after subscribing
+        let review_task = {
+            let desk = Arc::clone(&desk);
+            tokio::spawn(async move {
+                let msg = QueuedMessage::new(
+                    "msg-1".to_string(),
+                    "Alice".to_string(),
+                    "Deploy?".to_string(),
+                    0,
+                );
+                desk.review(msg).await
+            })
+        };
+        while desk.pending().is_none() {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        writer
+            .write_all(b"{\"type\":\"subscribe_reviews\"}\n")
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::ReviewsSubscribed
+        ));
+        match read_response(&mut reader).await {
+            InjectResponse::ReviewRequest { id, body, .. } => {
+                assert_eq!(id, "msg-1");
+                assert_eq!(body, "Deploy?");
+            }
+            other => panic!("Expected review_request, got {:?}", other),
+        }
+
+        let reject = r#"{"type":"review","id":"msg-1","decision":"reject","reason":"not now"}"#;
+        writer
+            .write_all(format!("{}\n", reject).as_bytes())
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::ReviewResult {
+                decision: ReviewAction::Reject,
+                ..
+            }
+        ));
+        assert_eq!(
+            review_task.await.unwrap(),
+            Some(ReviewDecision::Reject("not now".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_forwards_captured_reply() {
+        let (ctx, _channels) = test_context(10);
+        let queue = Arc::clone(&ctx.queue);
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server_handle = tokio::spawn(async move {
+            handle_connection(server_stream, ctx).await.unwrap();
+        });
+
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let inject = r#"{"type":"inject","id":"q-1","from":"Lead","body":"Status?","capture_response":true}"#;
+        writer
+            .write_all(format!("{}\n", inject).as_bytes())
+            .await
+            .unwrap();
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::InjectResult {
+                status: InjectStatus::Queued,
+                ..
+            }
+        ));
+        assert!(queue.dequeue().await.unwrap().sync.is_some());
+
+        queue.report_result("q-1".to_string(), InjectStatus::Delivered, None);
+        queue.report_captured("other".to_string(), "not ours".to_string(), false);
+        queue.report_captured("q-1".to_string(), "All green".to_string(), false);
+
+        assert!(matches!(
+            read_response(&mut reader).await,
+            InjectResponse::InjectResult {
+                status: InjectStatus::Delivered,
+                ..
+            }
+        ));
+        match read_response(&mut reader).await {
+            InjectResponse::CapturedResponse { id, text, .. } => {
+                assert_eq!(id, "q-1");
+                assert_eq!(text, "All green");
+            }
+            other => panic!("Expected captured_response, got {:?}", other),
+        }
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_start_wait_idle() {
+        let (ctx, mut channels) = test_context(1);
+        let responder = tokio::spawn(async move {
+            // Busy twice, then idle
+            let mut polls = 0;
+            while let Some(query) = channels.status_rx.recv().await {
+                polls += 1;
+                let _ = query.response_tx.send(StatusInfo {
+                    agent_idle: polls > 2,
+                    idle_method: (polls > 2).then_some(IdleMethod::Silence),
+                    paused: false,
+                    user_active: false,
+                    queue_length: 0,
+                    ready: 0,
+                    scheduled: 0,
+                    max_size: 1,
+                    seen_count: 0,
+                    cursor_position: None,
+                    last_output_ms: 0,
+                });
+            }
+            polls
+        });
+
+        let wait = InjectRequest::WaitIdle {
+            id: Some("w-1".to_string()),
+            timeout_ms: Some(5000),
+            after_busy: false,
+        };
+        match start_wait(wait, &ctx).unwrap().await {
+            InjectResponse::WaitResult {
+                id,
+                matched,
+                reason,
+                ..
+            } => {
+                assert_eq!(id.as_deref(), Some("w-1"));
+                assert!(matched);
+                assert!(reason.is_none());
+            }
+            other => panic!("Expected wait_result, got {:?}", other),
+        }
+
+        let wait = InjectRequest::WaitIdle {
+            id: None,
+            timeout_ms: Some(0),
+            after_busy: false,
+        };
+        match start_wait(wait, &ctx).unwrap().await {
+            InjectResponse::WaitResult { matched, .. } => assert!(matched),
+            other => panic!("Expected wait_result, got {:?}", other),
+        }
+
+        drop(ctx);
+        assert_eq!(responder.await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_start_wait_idle_after_busy() {
+        let (ctx, mut channels) = test_context(1);
+        let responder = tokio::spawn(async move {
+            // Idle, busy, then idle again
+            let mut polls = 0;
+            while let Some(query) = channels.status_rx.recv().await {
+                polls += 1;
+                let idle = polls != 2;
+                let _ = query.response_tx.send(StatusInfo {
+                    agent_idle: idle,
+                    idle_method: idle.then_some(IdleMethod::Silence),
+                    paused: false,
+                    user_active: false,
+                    queue_length: 0,
+                    ready: 0,
+                    scheduled: 0,
+                    max_size: 1,
+                    seen_count: 0,
+                    cursor_position: None,
+                    last_output_ms: 0,
+                });
+            }
+            polls
+        });
+
+        let wait = InjectRequest::WaitIdle {
+            id: None,
+            timeout_ms: Some(5000),
+            after_busy: true,
+        };
+        match start_wait(wait, &ctx).unwrap().await {
+            InjectResponse::WaitResult { matched, .. } => assert!(matched),
+            other => panic!("Expected wait_result, got {:?}", other),
+        }
+        drop(ctx);
+        // Not the first idle poll, but the one after the busy one
+        assert_eq!(responder.await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_wait_for_pattern() {
+        let (ctx, _channels) = test_context(1);
+        let output = Arc::clone(&ctx.output);
+        output.publish(b"Tests passed before the wait\r\n");
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server_handle = tokio::spawn(async move {
+            handle_connection(server_stream, ctx).await.unwrap();
+        });
+
+        let (reader, mut writer) = client_stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"type\":\"wait_for_pattern\",\"pattern\":\"(\"}\n")
+            .await
+            .unwrap();
+        match read_response(&mut reader).await {
+            InjectResponse::Error { message } => assert!(message.contains("Invalid pattern")),
+            other => panic!("Expected error, got {:?}", other),
+        }
+
+        // Two waits at once; the short one times out first
+        let slow = r#"{"type":"wait_for_pattern","id":"tests","pattern":"Tests (passed|failed): \\d+","timeout_ms":5000}"#;
+        let fast = r#"{"type":"wait_for_pattern","id":"never","pattern":"never","timeout_ms":50}"#;
+        writer
+            .write_all(format!("{}\n{}\n", slow, fast).as_bytes())
+            .await
+            .unwrap();
+        match read_response(&mut reader).await {
+            InjectResponse::WaitResult {
+                id,
+                matched,
+                reason,
+                ..
+            } => {
+                assert_eq!(id.as_deref(), Some("never"));
+                assert!(!matched);
+                assert!(reason.unwrap().contains("within 50ms"));
+            }
+            other => panic!("Expected wait_result, got {:?}", other),
+        }
+
+        // Matched against stripped output, even when split across chunks
+        output.publish(b"\x1b[32mTests pa");
+        output.publish(b"ssed\x1b[0m: 12\r\n");
+        match read_response(&mut reader).await {
+            InjectResponse::WaitResult {
+                id, matched, text, ..
+            } => {
+                assert_eq!(id.as_deref(), Some("tests"));
+                assert!(matched);
+                assert_eq!(text.as_deref(), Some("Tests passed: 12"));
+            }
+            other => panic!("Expected wait_result, got {:?}", other),
+        }
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_send_keys() {
+        let (ctx, mut channels) = test_context(1);
+        let json = r#"{"type":"send_keys","id":"k-1","keys":[{"key":"escape"},{"text":"2"},{"key":"up"},{"key":"enter"}]}"#;
+        let request: InjectRequest = serde_json::from_str(json).unwrap();
+
+        match handle_request(request.clone(), &ctx).await {
+            InjectResponse::SendKeysResult {
+                id, success, bytes, ..
+            } => {
+                assert_eq!(id.as_deref(), Some("k-1"));
+                assert!(success);
+                assert_eq!(bytes, 6);
+            }
+            other => panic!("Expected send_keys_result, got {:?}", other),
+        }
+        assert_eq!(channels.pty_rx.recv().await.unwrap(), b"\x1b2\x1b[A\r");
+
+        // The child switched to application cursor keys
+        ctx.modes.observe(b"\x1b[?1h");
+        handle_request(request, &ctx).await;
+        assert_eq!(channels.pty_rx.recv().await.unwrap(), b"\x1b2\x1bOA\r");
+
+        let unknown = r#"{"type":"send_keys","keys":[{"key":"warp"}]}"#;
+        match handle_request(serde_json::from_str(unknown).unwrap(), &ctx).await {
+            InjectResponse::Error { message } => assert!(message.contains("warp")),
+            other => panic!("Expected error, got {:?}", other),
+        }
+        let empty = r#"{"type":"send_keys","keys":[]}"#;
+        assert!(matches!(
+            handle_request(serde_json::from_str(empty).unwrap(), &ctx).await,
+            InjectResponse::Error { .. }
+        ));
+        assert!(channels.pty_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_screen() {
+        let (ctx, _channels) = test_context(1);
+        ctx.screen
+            .feed(b"\x1b[2J\x1b[HWorking...\r\n\x1b[1mDone.\x1b[0m\r\n> ");
+
+        let request: InjectRequest = serde_json::from_str(r#"{"type":"screen"}"#).unwrap();
+        match handle_request(request, &ctx).await {
+            InjectResponse::Screen {
+                rows,
+                cols,
+                cursor,
+                alternate_screen,
+                lines,
+                ..
+            } => {
+                assert_eq!((rows, cols), (24, 80));
+                assert_eq!(cursor, [2, 2]);
+                assert!(!alternate_screen);
+                assert_eq!(&lines[..3], &["Working...", "Done.", ">"]);
+                assert_eq!(lines.len(), 24);
+            }
+            other => panic!("Expected screen, got {:?}", other),
+        }
+    }
 }
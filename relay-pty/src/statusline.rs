@@ -0,0 +1,266 @@
+//! Status line overlay for interactive mode.
+//!
+//! Reserves the bottom row of the user's terminal with a scroll region
+//! (DECSTBM) and draws relay state there: agent name, idle/busy, queue length
+//! and the last delivery outcome. The child is told the terminal has one row
+//! fewer, so it never writes to the reserved row. The child's own scroll
+//! region, cursor and attributes are taken from the screen model, so drawing
+//! leaves its DECSTBM and DECSC state alone. Drawing is skipped while the
+//! child's last output ended in the middle of an escape sequence or UTF-8
+//! character, and while the terminal is too small to give up a row.
+
+use crate::inject::Injector;
+use crate::protocol::short_id;
+use crate::queue::MessageQueue;
+use crate::screen::Screen;
+
+/// Bottom-row status display
+#[derive(Debug)]
+pub struct StatusLine {
+    /// Rows of the real terminal (the status line is on the last one)
+    rows: u16,
+    /// Columns of the real terminal
+    cols: u16,
+    /// Text currently on screen, if drawn
+    drawn: Option<String>,
+}
+
+impl StatusLine {
+    /// Create a status line for a terminal of this size
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            rows,
+            cols,
+            drawn: None,
+        }
+    }
+
+    /// Whether the terminal is big enough to give up a row
+    pub fn is_shown(&self) -> bool {
+        self.rows >= 2
+    }
+
+    /// Rows reported to the child
+    pub fn child_rows(&self) -> u16 {
+        if self.is_shown() {
+            self.rows - 1
+        } else {
+            self.rows
+        }
+    }
+
+    /// Columns reported to the child
+    pub fn cols(&self) -> u16 {
+        self.cols
+    }
+
+    /// Adapt to a new terminal size. Returns whether the line is shown at it.
+    pub fn resize(&mut self, rows: u16, cols: u16) -> bool {
+        self.rows = rows;
+        self.cols = cols;
+        self.drawn = None;
+        self.is_shown()
+    }
+
+    /// Bytes that reserve the bottom row. The screen is cleared and the cursor
+    /// homed, so the terminal starts out matching the (blank) screen model.
+    pub fn setup(&self) -> Vec<u8> {
+        format!("\x1b[2J\x1b[1;{}r\x1b[H", self.rows - 1).into_bytes()
+    }
+
+    /// Bytes that draw `text` (truncated or padded to the terminal width).
+    /// The child's scroll region is set again, since it may have reset it to
+    /// the whole terminal, and its cursor and attributes are put back after.
+    pub fn draw(&mut self, text: &str, screen: &Screen) -> Vec<u8> {
+        let width = self.cols as usize;
+        let mut line: String = text.chars().take(width).collect();
+        let len = line.chars().count();
+        line.extend(std::iter::repeat_n(' ', width - len));
+
+        // Never let the region reach the bottom row, even mid-resize
+        let (top, bottom) = screen.scroll_region();
+        let bottom = bottom.min(self.rows - 2);
+        let top = top.min(bottom);
+        let mut out = format!(
+            "\x1b[{};{}r\x1b[{};1H\x1b[0m\x1b[2K\x1b[7m{}",
+            top + 1,
+            bottom + 1,
+            self.rows,
+            line
+        )
+        .into_bytes();
+        out.extend_from_slice(&screen.restore_cursor());
+        self.drawn = Some(text.to_string());
+        out
+    }
+
+    /// Whether `text` differs from what is on screen
+    pub fn is_stale(&self, text: &str) -> bool {
+        self.drawn.as_deref() != Some(text)
+    }
+
+    /// Forget what is drawn (the child may have drawn over it)
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// Bytes that release the bottom row and clear it
+    pub fn teardown(&self, screen: &Screen) -> Vec<u8> {
+        let mut out = format!("\x1b[r\x1b[{};1H\x1b[0m\x1b[2K", self.rows).into_bytes();
+        out.extend_from_slice(&screen.restore_cursor());
+        out
+    }
+}
+
+/// Describe the current relay state for the status line
+pub async fn describe(agent: &str, injector: &Injector, queue: &MessageQueue) -> String {
+    let stats = queue.stats().await;
+
+    let state = if injector.check_idle() {
+        "idle"
+    } else {
+        "busy"
+    };
+    let mut parts = vec![format!("relay-pty: {}", agent), state.to_string()];
+
+    let mut queue_part = format!("queue {}", stats.queue_length);
+    if stats.scheduled > 0 {
+        queue_part.push_str(&format!(" ({} scheduled)", stats.scheduled));
+    }
+    parts.push(queue_part);
+
+    if let Some((id, status)) = queue.last_finished() {
+        parts.push(format!("last {} {}", short_id(&id), status.as_str()));
+    }
+
+    if let Some(msg) = injector.awaiting_review() {
+        parts.push(format!("REVIEW {} (Ctrl-] a/m/x)", short_id(&msg.id)));
+    }
+
+    if injector.is_paused() {
+        parts.push("PAUSED".to_string());
+    } else if injector.auto_suggestion_visible() {
+        parts.push("blocked: auto-suggestion".to_string());
+    } else if injector.user_active() {
+        parts.push("waiting: user typing".to_string());
+    }
+
+    format!(" {} ", parts.join(" | "))
+}
+
+/// Whether output ended somewhere the status line can be drawn without
+/// splitting an escape sequence or a UTF-8 character
+pub fn ends_cleanly(chunk: &[u8]) -> bool {
+    // Incomplete UTF-8 character at the end
+    let mut start = chunk.len();
+    while start > 0 && chunk.len() - start < 3 && (chunk[start - 1] & 0xC0) == 0x80 {
+        start -= 1;
+    }
+    let continuation = chunk.len() - start;
+    if start > 0 {
+        let needed = match chunk[start - 1] {
+            0xC0..=0xDF => 1,
+            0xE0..=0xEF => 2,
+            0xF0..=0xF7 => 3,
+            _ => 0,
+        };
+        if continuation < needed {
+            return false;
+        }
+    }
+
+    // Incomplete escape sequence at the end
+    let Some(esc) = chunk.iter().rposition(|&b| b == 0x1b) else {
+        return true;
+    };
+    let rest = &chunk[esc + 1..];
+    match rest.first() {
+        None => false,
+        // CSI: ends with a final byte
+        Some(b'[') => rest[1..].iter().any(|b| (0x40..=0x7e).contains(b)),
+        // OSC / DCS / APC / PM: ends with BEL (ESC \ is handled as its own ESC)
+        Some(b']' | b'P' | b'_' | b'^') => rest.contains(&0x07),
+        // Character set designation: one more byte
+        Some(b'(' | b')' | b'*' | b'+') => rest.len() >= 2,
+        Some(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserves_bottom_row() {
+        let mut line = StatusLine::new(24, 20);
+        let screen = Screen::new(line.child_rows(), line.cols());
+        assert_eq!(line.child_rows(), 23);
+        assert!(String::from_utf8(line.setup())
+            .unwrap()
+            .contains("\x1b[1;23r"));
+
+        let drawn = String::from_utf8(line.draw("hello", &screen)).unwrap();
+        assert!(drawn.contains("\x1b[24;1H"));
+        assert!(drawn.contains(&format!("hello{}", " ".repeat(15))));
+        assert!(!line.is_stale("hello"));
+        assert!(line.is_stale("bye"));
+
+        assert!(line.resize(10, 4));
+        screen.resize(line.child_rows(), line.cols());
+        let drawn = String::from_utf8(line.draw("truncated", &screen)).unwrap();
+        assert!(drawn.contains("\x1b[1;9r"));
+        assert!(drawn.contains("\x1b[7mtrun"));
+
+        assert!(!line.resize(1, 80));
+        assert_eq!(line.child_rows(), 1);
+        assert!(line.resize(24, 80));
+        assert_eq!(line.child_rows(), 23);
+    }
+
+    #[test]
+    fn test_draw_keeps_child_scroll_region_and_cursor() {
+        let mut line = StatusLine::new(24, 80);
+        let screen = Screen::new(line.child_rows(), line.cols());
+        // The child sets its own region, moves and colours the cursor
+        screen.feed(b"\x1b[3;10r\x1b[5;7H\x1b[31m");
+
+        let drawn = String::from_utf8(line.draw("hello", &screen)).unwrap();
+        assert!(drawn.starts_with("\x1b[3;10r"));
+        assert!(drawn.ends_with("\x1b[5;7H\x1b[0;31m"));
+        // The child's saved cursor slot is never touched
+        assert!(!drawn.contains("\x1b7") && !drawn.contains("\x1b8"));
+
+        // A region reset by the child is clamped above the status row
+        screen.feed(b"\x1b[r");
+        let drawn = String::from_utf8(line.draw("hello", &screen)).unwrap();
+        assert!(drawn.starts_with("\x1b[1;23r"));
+
+        let teardown = String::from_utf8(line.teardown(&screen)).unwrap();
+        assert!(!teardown.contains("\x1b7") && !teardown.contains("\x1b8"));
+    }
+
+    #[test]
+    fn test_draw_keeps_pending_wrap() {
+        let mut line = StatusLine::new(5, 4);
+        let screen = Screen::new(line.child_rows(), line.cols());
+        screen.feed(b"abcd");
+
+        let drawn = String::from_utf8(line.draw("hi", &screen)).unwrap();
+        // The last cell is printed again so the next character still wraps
+        assert!(drawn.ends_with("\x1b[1;4H\x1b[0md\x1b[0m"));
+    }
+
+    #[test]
+    fn test_ends_cleanly() {
+        assert!(ends_cleanly(b"plain text"));
+        assert!(ends_cleanly(b"\x1b[31mred\x1b[0m"));
+        assert!(ends_cleanly("é".as_bytes()));
+        assert!(ends_cleanly(b"\x1b]0;title\x07"));
+
+        assert!(!ends_cleanly(b"text\x1b"));
+        assert!(!ends_cleanly(b"text\x1b[3"));
+        assert!(!ends_cleanly(b"\x1b]0;tit"));
+        assert!(!ends_cleanly(&"é".as_bytes()[..1]));
+        assert!(!ends_cleanly(&"🚀".as_bytes()[..3]));
+    }
+}
@@ -0,0 +1,258 @@
+//! Message templates for injected relay messages.
+//!
+//! A template is a format string with `{field}` placeholders:
+//!
+//! | Placeholder  | Value                                   |
+//! |--------------|-----------------------------------------|
+//! | `{from}`     | Sender name                             |
+//! | `{id}`       | Full message ID                         |
+//! | `{short_id}` | First 7 characters of the message ID    |
+//! | `{thread}`   | Thread identifier (empty if none)       |
+//! | `{priority}` | Message priority                        |
+//! | `{retries}`  | Number of previous injection attempts   |
+//! | `{body}`     | Message body                            |
+//!
+//! Retries are escalated by prepending a retry prefix (first retry) or an
+//! urgent prefix (later retries). Echo detection is derived from the literal
+//! text the template starts with, so changing the template keeps verification
+//! and idle tracking working.
+
+use crate::protocol::{short_id, QueuedMessage};
+use anyhow::{bail, Result};
+
+/// Default message format
+pub const DEFAULT_TEMPLATE: &str = "Relay message from {from} [{short_id}]: {body}";
+
+/// Default prefix for the first retry
+pub const DEFAULT_RETRY_PREFIX: &str = "[RETRY] ";
+
+/// Default prefix for the second and later retries
+pub const DEFAULT_URGENT_PREFIX: &str = "[URGENT - PLEASE ACKNOWLEDGE] ";
+
+/// A value substituted into the template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    From,
+    Id,
+    ShortId,
+    Thread,
+    Priority,
+    Retries,
+    Body,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "from" => Field::From,
+            "id" => Field::Id,
+            "short_id" => Field::ShortId,
+            "thread" => Field::Thread,
+            "priority" => Field::Priority,
+            "retries" => Field::Retries,
+            "body" => Field::Body,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// Parsed message template with retry escalation prefixes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageTemplate {
+    segments: Vec<Segment>,
+    retry_prefix: String,
+    urgent_prefix: String,
+}
+
+impl Default for MessageTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("default template is valid")
+    }
+}
+
+impl MessageTemplate {
+    /// Parse a template with the default escalation prefixes.
+    /// Fails on unknown placeholders, unclosed braces or a missing `{body}`.
+    pub fn parse(format: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = format;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let Some(close) = rest[open..].find('}') else {
+                bail!("Unclosed '{{' in message template: {}", format);
+            };
+            let name = &rest[open + 1..open + close];
+            match Field::parse(name) {
+                Some(field) => segments.push(Segment::Field(field)),
+                None => bail!("Unknown placeholder {{{}}} in message template", name),
+            }
+            rest = &rest[open + close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        if !segments.contains(&Segment::Field(Field::Body)) {
+            bail!("Message template must contain {{body}}: {}", format);
+        }
+
+        Ok(Self {
+            segments,
+            retry_prefix: DEFAULT_RETRY_PREFIX.to_string(),
+            urgent_prefix: DEFAULT_URGENT_PREFIX.to_string(),
+        })
+    }
+
+    /// Replace the retry escalation prefixes
+    pub fn with_prefixes(mut self, retry_prefix: String, urgent_prefix: String) -> Self {
+        self.retry_prefix = retry_prefix;
+        self.urgent_prefix = urgent_prefix;
+        self
+    }
+
+    /// Render a queued message, escalating by retry count.
+    ///
+    /// Bodies that already start like a rendered message (e.g. formatted by the
+    /// Node.js orchestrator) are used as-is, with only the escalation prefix added.
+    pub fn render(&self, msg: &QueuedMessage) -> String {
+        let base = if self.is_formatted(&msg.body) {
+            msg.body.clone()
+        } else {
+            let mut out = String::with_capacity(msg.body.len() + 64);
+            for segment in &self.segments {
+                match segment {
+                    Segment::Literal(text) => out.push_str(text),
+                    Segment::Field(field) => match field {
+                        Field::From => out.push_str(&msg.from),
+                        Field::Id => out.push_str(&msg.id),
+                        Field::ShortId => out.push_str(short_id(&msg.id)),
+                        Field::Thread => out.push_str(msg.thread.as_deref().unwrap_or("")),
+                        Field::Priority => out.push_str(&msg.priority.to_string()),
+                        Field::Retries => out.push_str(&msg.retries.to_string()),
+                        Field::Body => out.push_str(&msg.body),
+                    },
+                }
+            }
+            out
+        };
+
+        match msg.retries {
+            0 => base,
+            1 => format!("{}{}", self.retry_prefix, base),
+            _ => format!("{}{}", self.urgent_prefix, base),
+        }
+    }
+
+    /// Literal text every rendered message starts with (before the first placeholder)
+    pub fn echo_prefix(&self) -> &str {
+        match self.segments.first() {
+            Some(Segment::Literal(text)) => text,
+            _ => "",
+        }
+    }
+
+    /// Whether the text already starts like a rendered message
+    fn is_formatted(&self, text: &str) -> bool {
+        let prefix = self.echo_prefix();
+        !prefix.trim().is_empty() && text.starts_with(prefix)
+    }
+
+    /// Whether a line of output is an echo of an injected message
+    /// (optionally preceded by an escalation prefix)
+    pub fn is_echo_line(&self, line: &str) -> bool {
+        let trimmed = line.trim();
+        let unprefixed = trimmed
+            .strip_prefix(self.urgent_prefix.trim_end())
+            .or_else(|| trimmed.strip_prefix(self.retry_prefix.trim_end()))
+            .map(str::trim_start)
+            .unwrap_or(trimmed);
+        self.is_formatted(unprefixed)
+    }
+
+    /// Whether output consists only of message echoes and blank lines
+    pub fn is_echo(&self, output: &str) -> bool {
+        output.lines().all(|line| {
+            let trimmed = line.trim();
+            trimmed.is_empty() || self.is_echo_line(trimmed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(retries: u32) -> QueuedMessage {
+        let mut msg = QueuedMessage::new(
+            "abc1234567".to_string(),
+            "Alice".to_string(),
+            "Ship it".to_string(),
+            2,
+        );
+        msg.retries = retries;
+        msg.thread = Some("release".to_string());
+        msg
+    }
+
+    #[test]
+    fn test_default_template_matches_legacy_format() {
+        let template = MessageTemplate::default();
+        assert_eq!(
+            template.render(&message(0)),
+            "Relay message from Alice [abc1234]: Ship it"
+        );
+        assert_eq!(
+            template.render(&message(1)),
+            "[RETRY] Relay message from Alice [abc1234]: Ship it"
+        );
+        assert_eq!(
+            template.render(&message(3)),
+            "[URGENT - PLEASE ACKNOWLEDGE] Relay message from Alice [abc1234]: Ship it"
+        );
+    }
+
+    #[test]
+    fn test_custom_template_fields() {
+        let template =
+            MessageTemplate::parse("<msg from=\"{from}\" id=\"{id}\" thread=\"{thread}\" p={priority} try={retries}>{body}</msg>")
+                .unwrap()
+                .with_prefixes("(again) ".to_string(), "(!!) ".to_string());
+
+        assert_eq!(
+            template.render(&message(1)),
+            "(again) <msg from=\"Alice\" id=\"abc1234567\" thread=\"release\" p=2 try=1>Ship it</msg>"
+        );
+        assert_eq!(template.echo_prefix(), "<msg from=\"");
+        assert!(template.is_echo("<msg from=\"Bob\" id=\"x\">hi</msg>\n"));
+        assert!(template.is_echo("(!!) <msg from=\"Bob\""));
+        assert!(!template.is_echo("Relay message from Bob [x]: hi"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(MessageTemplate::parse("{from}: no body").is_err());
+        assert!(MessageTemplate::parse("{sender}: {body}").is_err());
+        assert!(MessageTemplate::parse("{from: {body}").is_err());
+    }
+
+    #[test]
+    fn test_template_without_literal_prefix_never_matches_echo() {
+        let template = MessageTemplate::parse("{from}: {body}").unwrap();
+        assert_eq!(template.echo_prefix(), "");
+        assert!(!template.is_echo("Alice: hi"));
+        assert!(template.is_echo("\n\n"));
+        // Nothing looks pre-formatted, so the body is always wrapped
+        let mut msg = message(0);
+        msg.body = "Alice: hi".to_string();
+        assert_eq!(template.render(&msg), "Alice: Alice: hi");
+    }
+}
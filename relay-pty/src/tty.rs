@@ -0,0 +1,83 @@
+//! The controlling terminal of this process.
+//!
+//! Raw mode for transparent passthrough and size queries, used both when
+//! relay-pty runs in the foreground of a terminal and by `relay-pty attach`.
+
+use anyhow::Result;
+use nix::libc;
+use nix::pty::Winsize;
+use tracing::debug;
+
+/// Original terminal settings, restored on exit
+static mut ORIGINAL_TERMIOS: Option<libc::termios> = None;
+
+/// Check if stdin is a TTY
+pub fn is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) != 0 }
+}
+
+/// Set raw mode on stdin for transparent terminal passthrough.
+/// Returns Ok(false) if stdin is not a TTY (headless mode).
+/// Returns Ok(true) if raw mode was successfully set.
+pub fn set_raw_mode() -> Result<bool> {
+    let stdin_fd = libc::STDIN_FILENO;
+
+    // Check if stdin is a TTY - if not, skip raw mode (headless mode)
+    if !is_tty() {
+        debug!("stdin is not a TTY, skipping raw mode (headless mode)");
+        return Ok(false);
+    }
+
+    unsafe {
+        // Save original settings
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(stdin_fd, &mut termios) != 0 {
+            anyhow::bail!("Failed to get terminal attributes");
+        }
+        ORIGINAL_TERMIOS = Some(termios);
+
+        // Set raw mode
+        let mut raw = termios;
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_oflag &= !(libc::OPOST);
+
+        if libc::tcsetattr(stdin_fd, libc::TCSANOW, &raw) != 0 {
+            anyhow::bail!("Failed to set raw mode");
+        }
+    }
+
+    debug!("Terminal set to raw mode");
+    Ok(true)
+}
+
+/// Restore original terminal settings
+pub fn restore_terminal() {
+    unsafe {
+        if let Some(ref termios) = ORIGINAL_TERMIOS {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            debug!("Terminal restored");
+        }
+    }
+}
+
+/// Get current terminal size as (rows, cols), if known (not 0x0)
+pub fn terminal_size() -> Option<(u16, u16)> {
+    let mut winsize = Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    unsafe {
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut winsize) == 0
+            && winsize.ws_row > 0
+            && winsize.ws_col > 0
+        {
+            Some((winsize.ws_row, winsize.ws_col))
+        } else {
+            None
+        }
+    }
+}